@@ -0,0 +1,82 @@
+//! Bytes a player gets each tick, the whole view against keyframes plus deltas. Run with
+//! `cargo bench -p snake --bench bandwidth`
+use snake::{
+    delta::{DeltaEncoder, Frame},
+    game::{Game, GameData},
+    interest::Interest,
+    rng::Rng,
+    types::{Direction, FieldPoint, GameConfig, GameState},
+    utils::encode,
+};
+
+const TICKS: usize = 1000;
+const KEYFRAME_EVERY: u32 = 64;
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+];
+
+/// Average bytes per tick of the full view and of the delta frames, along with the keyframes
+/// and games it took
+fn measure(snakes: usize) -> (usize, usize, usize, u64) {
+    let config = GameConfig {
+        dim: (120, 80),
+        ..GameConfig::default()
+    };
+    // the window covers the field, so every snake shows up in the view
+    let window = config.dim;
+    let mut game = Game::new(config);
+    for _ in 0..snakes {
+        game.add_snake().expect("no room for the snake");
+    }
+    game.state = GameState::Playing;
+
+    let mut rng = Rng::new(1);
+    let mut encoder = DeltaEncoder::new(KEYFRAME_EVERY);
+    let (mut full, mut delta, mut keyframes) = (0, 0, 0);
+    let mut rounds = 1;
+    for _ in 0..TICKS {
+        // a game that's over sends nothing new, the next one starts right away
+        if game.state == GameState::Over {
+            game = game.rematch(rounds);
+            game.state = GameState::Playing;
+            rounds += 1;
+        }
+        assert_eq!(game.state, GameState::Playing);
+        let ids: Vec<u16> = game.snakes.keys().copied().collect();
+        for id in ids {
+            if rng.next_u64() % 8 == 0 {
+                let to = DIRECTIONS[(rng.next_u64() % 4) as usize];
+                game.head_to(id, to);
+            }
+            // keeps the number of snakes up
+            if !game.snakes[&id].is_alive() {
+                game.remove_snake(id);
+                let _ = game.add_snake();
+            }
+        }
+        game.tick();
+
+        let interest = Interest::new(GameData::from_game(&game), window, (8, 4));
+        let view = interest.view(None, &FieldPoint { x: 0, y: 0 });
+        full += encode(view.clone()).unwrap().len();
+        let frame = encoder.frame(view);
+        if let Frame::Keyframe { .. } = frame {
+            keyframes += 1;
+        }
+        delta += encode(frame).unwrap().len();
+    }
+    (full / TICKS, delta / TICKS, keyframes, rounds)
+}
+
+fn main() {
+    println!("bytes/tick over {TICKS} ticks, a keyframe every {KEYFRAME_EVERY}");
+    println!("snakes        full       delta   keyframes   games");
+    for snakes in [2, 10, 50] {
+        let (full, delta, keyframes, games) = measure(snakes);
+        let ratio = delta as f64 * 100.0 / full as f64;
+        println!("{snakes:>6} {full:>11} {delta:>11} {keyframes:>11} {games:>7}   {ratio:.1}%");
+    }
+}
@@ -0,0 +1,139 @@
+//! Food placement on a large, half filled field. Compares the free cells index with the
+//! approach it replaced: flat bit sets for snakes, walls and portals, scanned up to the nth free
+//! cell, with foods searched one by one in a list. That code took u16 indices, so it's carried
+//! over here with wider ones to fit the field. Run with `cargo bench -p snake --bench food`
+use std::{hint::black_box, time::Instant};
+
+use fixedbitset::FixedBitSet;
+use snake::{
+    food::{FoodField, FoodRules},
+    rng::Rng,
+    types::{Field, FieldPoint, Food, FoodType},
+};
+
+const SIZE: u32 = 1000;
+const FOODS: usize = 200;
+
+/// The field as it was before the free cells index
+struct Baseline {
+    width: u32,
+    bit_set: FixedBitSet,
+    walls: FixedBitSet,
+    portals: FixedBitSet,
+    foods: Vec<Food>,
+}
+
+impl Baseline {
+    fn point(&self, idx: usize) -> FieldPoint {
+        FieldPoint {
+            x: (idx % self.width as usize) as u32,
+            y: (idx / self.width as usize) as u32,
+        }
+    }
+
+    fn idx_filled(&self, idx: usize) -> bool {
+        self.bit_set.contains(idx) || self.walls.contains(idx) || self.portals.contains(idx)
+    }
+
+    fn free_count(&self) -> usize {
+        let mut filled = self.bit_set.clone();
+        filled.union_with(&self.walls);
+        filled.union_with(&self.portals);
+        self.bit_set.len() - filled.count_ones(..)
+    }
+
+    fn has_at(&self, p: &FieldPoint) -> Option<usize> {
+        (0..self.foods.len()).find(|&i| self.foods[i].is_at(p))
+    }
+
+    fn total_filled(&self) -> usize {
+        self.foods.iter().map(|food| food.size as usize).sum()
+    }
+
+    /// Finds the nth free cell, then probes past food
+    fn add_food(&mut self, rng: &mut Rng, rules: &FoodRules) {
+        let max = self.free_count().saturating_sub(self.total_filled());
+        if max < 1 {
+            return;
+        }
+        let mut nth = rng.next_u64() as usize % max;
+        let mut idx = 0;
+        let len = self.bit_set.len();
+        while nth > 0 && idx < len {
+            if !self.idx_filled(idx) {
+                nth -= 1;
+            }
+            idx += 1;
+        }
+        while idx < len && (self.has_at(&self.point(idx)).is_some() || self.idx_filled(idx)) {
+            idx += 1;
+        }
+        if idx == len {
+            return;
+        }
+        let food = Food::new(FoodType::Basic, self.point(idx), rules);
+        self.foods.push(food);
+    }
+}
+
+/// Every other cell taken by a snake, at random
+fn half_filled(rng: &mut Rng) -> (Field, Baseline) {
+    let mut field = Field::new(SIZE, SIZE);
+    let len = SIZE as usize * SIZE as usize;
+    let mut baseline = Baseline {
+        width: SIZE,
+        bit_set: FixedBitSet::with_capacity(len),
+        walls: FixedBitSet::with_capacity(len),
+        portals: FixedBitSet::with_capacity(len),
+        foods: Vec::new(),
+    };
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if rng.next_u64() % 2 == 0 {
+                field.set(&FieldPoint { x, y }, true);
+                baseline.bit_set.insert((y * SIZE + x) as usize);
+            }
+        }
+    }
+    (field, baseline)
+}
+
+fn main() {
+    let rules = FoodRules {
+        special_every: 0,
+        ..FoodRules::DEFAULT
+    };
+    let (field, mut baseline) = half_filled(&mut Rng::new(1));
+
+    let mut rng = Rng::new(2);
+    let now = Instant::now();
+    for _ in 0..FOODS {
+        baseline.add_food(&mut rng, &rules);
+    }
+    let linear = now.elapsed();
+    assert_eq!(baseline.foods.len(), FOODS);
+    black_box(&baseline.foods);
+
+    let mut rng = Rng::new(2);
+    let mut food = FoodField::default();
+    food.minimum = FOODS as u16;
+    let now = Instant::now();
+    for _ in 0..FOODS {
+        food.add_food(&field, &mut rng, &rules);
+    }
+    let indexed = now.elapsed();
+    assert_eq!(food.foods.len(), FOODS);
+    black_box(&food);
+
+    println!("{FOODS} foods on a {SIZE}x{SIZE} field, half of it filled");
+    println!(
+        "baseline     {:>12?} {:>10?}/food",
+        linear,
+        linear / FOODS as u32
+    );
+    println!(
+        "free cells   {:>12?} {:>10?}/food",
+        indexed,
+        indexed / FOODS as u32
+    );
+}
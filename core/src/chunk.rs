@@ -0,0 +1,149 @@
+use fixedbitset::FixedBitSet;
+
+use crate::{
+    free_cells::FreeCells,
+    types::{FieldPoint, GameConfig},
+};
+
+/// Side of the square chunks the field is split into
+pub const CHUNK: u32 = 32;
+const CELLS: usize = (CHUNK * CHUNK) as usize;
+
+/// Column and row of a chunk, `(1, 0)` covers cells 32 to 63 of the first 32 rows
+pub type ChunkId = (u32, u32);
+
+pub fn chunk_of(p: &FieldPoint) -> ChunkId {
+    (p.x / CHUNK, p.y / CHUNK)
+}
+
+pub fn local_idx(p: &FieldPoint) -> usize {
+    ((p.y % CHUNK) * CHUNK + p.x % CHUNK) as usize
+}
+
+/// Occupancy of a piece of the field, allocated the first time something lands on it. Chunks on
+/// the right and bottom edges may be cut short, cells past the field are never free
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    pub width: u32,
+    pub height: u32,
+    pub snakes: FixedBitSet,
+    pub walls: FixedBitSet,
+    pub portals: FixedBitSet,
+    free: FreeCells,
+}
+
+impl Chunk {
+    pub fn new(width: u32, height: u32) -> Chunk {
+        let mut free = FreeCells::new(CELLS);
+        for idx in 0..CELLS {
+            let (x, y) = (idx as u32 % CHUNK, idx as u32 / CHUNK);
+            if x >= width || y >= height {
+                free.remove(idx);
+            }
+        }
+        Chunk {
+            width,
+            height,
+            snakes: FixedBitSet::with_capacity(CELLS),
+            walls: FixedBitSet::with_capacity(CELLS),
+            portals: FixedBitSet::with_capacity(CELLS),
+            free,
+        }
+    }
+
+    pub fn filled(&self, idx: usize) -> bool {
+        !self.free.contains(idx)
+    }
+
+    pub fn free(&self) -> &FreeCells {
+        &self.free
+    }
+
+    /// Syncs the free cells with the layers, returns whether the cell is filled now
+    pub fn refresh(&mut self, idx: usize) -> bool {
+        let filled =
+            self.snakes.contains(idx) || self.walls.contains(idx) || self.portals.contains(idx);
+        match filled {
+            true => self.free.remove(idx),
+            false => self.free.insert(idx),
+        }
+        filled
+    }
+
+    /// Cells within the field, the ones past its edges are left filled
+    pub fn refresh_all(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.refresh((y * CHUNK + x) as usize);
+            }
+        }
+    }
+
+    pub fn filled_count(&self) -> u64 {
+        (self.width * self.height) as u64 - self.free.len() as u64
+    }
+}
+
+/// Chunks a window of cells centered on some point touches, going around the edges the field
+/// wraps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkView {
+    pub center: ChunkId,
+    /// Chunks each way from the center one, columns and rows
+    pub radius: (u32, u32),
+    pub columns: u32,
+    pub rows: u32,
+    pub wrap_x: bool,
+    pub wrap_y: bool,
+}
+
+impl ChunkView {
+    pub fn new(center: &FieldPoint, window: (u32, u32), config: &GameConfig) -> ChunkView {
+        let (width, height) = config.dim;
+        ChunkView {
+            center: chunk_of(center),
+            radius: (
+                (window.0 / 2).div_ceil(CHUNK),
+                (window.1 / 2).div_ceil(CHUNK),
+            ),
+            columns: width.div_ceil(CHUNK),
+            rows: height.div_ceil(CHUNK),
+            wrap_x: config.wrap.wraps_x(),
+            wrap_y: config.wrap.wraps_y(),
+        }
+    }
+
+    pub fn contains(&self, p: &FieldPoint) -> bool {
+        let (x, y) = chunk_of(p);
+        let near = |a: u32, b: u32, len: u32, wraps: bool, radius: u32| {
+            let d = a.abs_diff(b);
+            let d = match wraps {
+                true => d.min(len - d),
+                false => d,
+            };
+            d <= radius
+        };
+        near(x, self.center.0, self.columns, self.wrap_x, self.radius.0)
+            && near(y, self.center.1, self.rows, self.wrap_y, self.radius.1)
+    }
+
+    /// Every chunk in view once, even when the window is wider than a wrapping field
+    pub fn chunks(&self) -> Vec<ChunkId> {
+        let axis = |center: u32, radius: u32, len: u32, wraps: bool| -> Vec<u32> {
+            if !wraps {
+                let last = center.saturating_add(radius).min(len - 1);
+                return (center.saturating_sub(radius)..=last).collect();
+            }
+            let start = (center + len - radius % len) % len;
+            let span = radius.saturating_mul(2).min(len - 1);
+            (0..=span)
+                .map(|d| ((start as u64 + d as u64) % len as u64) as u32)
+                .collect()
+        };
+        let xs = axis(self.center.0, self.radius.0, self.columns, self.wrap_x);
+        let ys = axis(self.center.1, self.radius.1, self.rows, self.wrap_y);
+        ys.iter()
+            .flat_map(|y| xs.iter().map(move |x| (*x, *y)))
+            .collect()
+    }
+}
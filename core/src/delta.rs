@@ -0,0 +1,296 @@
+//! Delta compressed views. A keyframe carries a whole `View`, the frames in between only what
+//! changed since the previous one. Frames are numbered so a client that misses one knows to ask
+//! for a keyframe, rather than drifting away from the server.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    effect::Effect,
+    error::Error,
+    interest::{Minimap, View},
+    snake::{Snake, SnakeNode},
+    types::{FieldPoint, Food, GameState, Wormhole, WrappableDirection},
+};
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone)]
+pub enum Frame {
+    Keyframe { seq: u32, view: Box<View> },
+    Delta { seq: u32, changes: Vec<Change> },
+}
+
+impl Frame {
+    pub fn seq(&self) -> u32 {
+        match self {
+            Frame::Keyframe { seq, .. } | Frame::Delta { seq, .. } => *seq,
+        }
+    }
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
+pub enum Change {
+    /// A snake came into view, or changed more than moving along
+    Snake(Snake),
+    /// A snake left the game or the view
+    Left(u16),
+    /// Tails popped, then heads pushed
+    Moved {
+        id: u16,
+        popped: u16,
+        pushed: Vec<SnakeNode>,
+    },
+    Turned {
+        id: u16,
+        direction: WrappableDirection,
+    },
+    Score {
+        id: u16,
+        score: u16,
+    },
+    Effects {
+        id: u16,
+        effects: Vec<Effect>,
+    },
+    /// New food, or one whose countdown went on, takes its location
+    FoodAdded(Food),
+    FoodRemoved(FieldPoint),
+    FoodCounters {
+        minimum: u16,
+        count: u16,
+    },
+    Walls(Vec<FieldPoint>),
+    Wormholes(Vec<Wormhole>),
+    State(GameState),
+    NextId(u16),
+    Minimap(Minimap),
+}
+
+/// What changed from `last` to `next`, their configs are expected to match
+pub fn diff(last: &View, next: &View) -> Vec<Change> {
+    let (old, new) = (&last.data, &next.data);
+    let mut changes = Vec::new();
+
+    for (id, snake) in new.snakes.iter() {
+        let Some(before) = old.snakes.get(id) else {
+            changes.push(Change::Snake(snake.clone()));
+            continue;
+        };
+        if before.state != snake.state {
+            changes.push(Change::Snake(snake.clone()));
+            continue;
+        }
+        let (popped, pushed) = moved(&before.nodes, &snake.nodes);
+        if popped > 0 || !pushed.is_empty() {
+            let popped = popped as u16;
+            changes.push(Change::Moved {
+                id: *id,
+                popped,
+                pushed,
+            });
+        }
+        if before.direction != snake.direction {
+            let direction = snake.direction;
+            changes.push(Change::Turned { id: *id, direction });
+        }
+        if before.score != snake.score {
+            let score = snake.score;
+            changes.push(Change::Score { id: *id, score });
+        }
+        if before.effects != snake.effects {
+            let effects = snake.effects.clone();
+            changes.push(Change::Effects { id: *id, effects });
+        }
+    }
+    let left = old.snakes.keys().filter(|id| !new.snakes.contains_key(id));
+    changes.extend(left.map(|id| Change::Left(*id)));
+
+    // removals go first, so the cells they free up can be taken by the added ones
+    let before: HashMap<FieldPoint, &Food> =
+        old.food.foods.iter().map(|f| (f.location, f)).collect();
+    let after: HashMap<FieldPoint, &Food> =
+        new.food.foods.iter().map(|f| (f.location, f)).collect();
+    let removed = before.keys().filter(|p| !after.contains_key(p));
+    changes.extend(removed.map(|p| Change::FoodRemoved(*p)));
+    let added = new
+        .food
+        .foods
+        .iter()
+        .filter(|f| before.get(&f.location) != Some(f));
+    changes.extend(added.map(|f| Change::FoodAdded(*f)));
+    if (old.food.minimum, old.food.count) != (new.food.minimum, new.food.count) {
+        let (minimum, count) = (new.food.minimum, new.food.count);
+        changes.push(Change::FoodCounters { minimum, count });
+    }
+
+    if old.walls != new.walls {
+        changes.push(Change::Walls(new.walls.clone()));
+    }
+    if old.wormholes != new.wormholes {
+        changes.push(Change::Wormholes(new.wormholes.clone()));
+    }
+    if old.state != new.state {
+        changes.push(Change::State(new.state.clone()));
+    }
+    if old.next_id != new.next_id {
+        changes.push(Change::NextId(new.next_id));
+    }
+    if last.minimap != next.minimap {
+        changes.push(Change::Minimap(next.minimap.clone()));
+    }
+    changes
+}
+
+/// Tails popped and heads pushed going from `before` to `after`. A body that didn't just move
+/// along is popped whole and pushed again
+fn moved(before: &VecDeque<SnakeNode>, after: &VecDeque<SnakeNode>) -> (usize, Vec<SnakeNode>) {
+    let popped = (0..before.len())
+        .find(|popped| {
+            let kept = before.len() - popped;
+            let mut same = before.iter().skip(*popped).zip(after.iter());
+            kept <= after.len() && same.all(|(a, b)| a == b)
+        })
+        .unwrap_or(before.len());
+    let kept = before.len() - popped;
+    (popped, after.iter().skip(kept).copied().collect())
+}
+
+pub fn apply(view: &mut View, changes: Vec<Change>) {
+    let data = &mut view.data;
+    for change in changes {
+        match change {
+            Change::Snake(snake) => {
+                data.snakes.insert(snake.id, snake);
+            }
+            Change::Left(id) => {
+                data.snakes.remove(&id);
+            }
+            Change::Moved { id, popped, pushed } => {
+                if let Some(snake) = data.snakes.get_mut(&id) {
+                    let popped = (popped as usize).min(snake.nodes.len());
+                    snake.nodes.drain(..popped);
+                    snake.nodes.extend(pushed);
+                }
+            }
+            Change::Turned { id, direction } => {
+                if let Some(snake) = data.snakes.get_mut(&id) {
+                    snake.direction = direction;
+                }
+            }
+            Change::Score { id, score } => {
+                if let Some(snake) = data.snakes.get_mut(&id) {
+                    snake.score = score;
+                }
+            }
+            Change::Effects { id, effects } => {
+                if let Some(snake) = data.snakes.get_mut(&id) {
+                    snake.effects = effects;
+                }
+            }
+            Change::FoodAdded(food) => {
+                let count = data.food.count;
+                data.food.grab(&food.location);
+                data.food.set_food(food);
+                data.food.count = count;
+            }
+            Change::FoodRemoved(p) => {
+                data.food.grab(&p);
+            }
+            Change::FoodCounters { minimum, count } => {
+                data.food.minimum = minimum;
+                data.food.count = count;
+            }
+            Change::Walls(walls) => data.walls = walls,
+            Change::Wormholes(wormholes) => data.wormholes = wormholes,
+            Change::State(state) => data.state = state,
+            Change::NextId(next_id) => data.next_id = next_id,
+            Change::Minimap(minimap) => view.minimap = minimap,
+        }
+    }
+}
+
+/// Server side of a connection, remembers the last view sent
+#[derive(Debug)]
+pub struct DeltaEncoder {
+    seq: u32,
+    last: Option<View>,
+    /// Frames between keyframes
+    keyframe_every: u32,
+    since_keyframe: u32,
+}
+
+impl DeltaEncoder {
+    pub fn new(keyframe_every: u32) -> DeltaEncoder {
+        DeltaEncoder {
+            seq: 0,
+            last: None,
+            keyframe_every,
+            since_keyframe: 0,
+        }
+    }
+
+    /// The next frame is a keyframe
+    pub fn resync(&mut self) {
+        self.last = None;
+    }
+
+    pub fn frame(&mut self, view: View) -> Frame {
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let frame = match &self.last {
+            Some(last)
+                if self.since_keyframe < self.keyframe_every
+                    && last.data.config == view.data.config =>
+            {
+                self.since_keyframe += 1;
+                let changes = diff(last, &view);
+                Frame::Delta { seq, changes }
+            }
+            _ => {
+                self.since_keyframe = 0;
+                let view = Box::new(view.clone());
+                Frame::Keyframe { seq, view }
+            }
+        };
+        self.last = Some(view);
+        frame
+    }
+}
+
+/// Client side of a connection, rebuilds the view frame by frame
+#[derive(Debug, Default)]
+pub struct DeltaDecoder {
+    seq: u32,
+    view: Option<View>,
+}
+
+impl DeltaDecoder {
+    pub fn view(&self) -> Option<&View> {
+        self.view.as_ref()
+    }
+
+    /// Only the first delta after a gap is an error, the ones after it are dropped quietly
+    /// while waiting for a keyframe. A frame leaving the view invalid drops it as well
+    pub fn apply(&mut self, frame: Frame) -> Result<(), Error> {
+        let got = frame.seq();
+        let view = match frame {
+            Frame::Keyframe { view, .. } => self.view.insert(*view),
+            Frame::Delta { changes, .. } => {
+                let Some(view) = &mut self.view else {
+                    return Ok(());
+                };
+                let expected = self.seq.wrapping_add(1);
+                if got != expected {
+                    self.view = None;
+                    return Err(Error::FrameGap { expected, got });
+                }
+                apply(view, changes);
+                view
+            }
+        };
+        if let Err(err) = view.data.validate() {
+            self.view = None;
+            return Err(err);
+        }
+        self.seq = got;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::types::FoodType;
+
+/// What eating a special food does to a snake for a while
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectKind {
+    /// Whale, eaten food scores double
+    Multiplier,
+    /// Turtle, moves every other tick
+    Slow,
+    /// Chameleon, passes through the body of other snakes
+    Ghost,
+    /// Elephant, keeps its tail every other tick
+    Grow,
+    /// Alien, turns the other way
+    Reversed,
+    /// Caterpillar, drops an extra tail node every other tick
+    Shrink,
+}
+
+impl EffectKind {
+    pub fn of(food: FoodType) -> Option<EffectKind> {
+        match food {
+            FoodType::Basic => None,
+            FoodType::Whale => Some(EffectKind::Multiplier),
+            FoodType::Turtle => Some(EffectKind::Slow),
+            FoodType::Chameleon => Some(EffectKind::Ghost),
+            FoodType::Elephant => Some(EffectKind::Grow),
+            FoodType::Alien => Some(EffectKind::Reversed),
+            FoodType::Caterpillar => Some(EffectKind::Shrink),
+        }
+    }
+
+    /// The food granting it, renders use its sprite
+    pub fn food(&self) -> FoodType {
+        match self {
+            EffectKind::Multiplier => FoodType::Whale,
+            EffectKind::Slow => FoodType::Turtle,
+            EffectKind::Ghost => FoodType::Chameleon,
+            EffectKind::Grow => FoodType::Elephant,
+            EffectKind::Reversed => FoodType::Alien,
+            EffectKind::Shrink => FoodType::Caterpillar,
+        }
+    }
+
+    pub fn duration(&self) -> u8 {
+        match self {
+            EffectKind::Multiplier => 30,
+            EffectKind::Slow => 20,
+            EffectKind::Ghost => 20,
+            EffectKind::Grow => 10,
+            EffectKind::Reversed => 15,
+            EffectKind::Shrink => 10,
+        }
+    }
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub ticks_left: u8,
+}
+
+impl Effect {
+    pub fn new(kind: EffectKind) -> Effect {
+        Effect {
+            kind,
+            ticks_left: kind.duration(),
+        }
+    }
+
+    /// Effects acting every other tick do it on even ticks left
+    pub fn on_beat(&self) -> bool {
+        self.ticks_left % 2 == 0
+    }
+}
@@ -0,0 +1,73 @@
+//! Errors of the fallible parts of the crate, mostly bytes that came from somewhere else.
+//! Parsers keep their own error types with line numbers, which convert into this one.
+
+use std::fmt;
+
+use bincode::error::DecodeError;
+
+use crate::{food::RulesError, map::MapError, replay::ReplayError};
+
+#[derive(Debug)]
+pub enum Error {
+    /// Bytes that aren't a bincoded value of the expected type
+    Decode(DecodeError),
+    /// Decoded fine but doesn't make a game, like a snake out of the field
+    InvalidConfig(String),
+    /// No room left for another snake
+    FieldFull,
+    /// A delta that doesn't follow the last frame
+    FrameGap {
+        expected: u32,
+        got: u32,
+    },
+    Map(MapError),
+    Rules(RulesError),
+    Replay(ReplayError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Decode(err) => write!(f, "can't decode: {err}"),
+            Error::InvalidConfig(message) => write!(f, "invalid config: {message}"),
+            Error::FieldFull => write!(f, "no room left on the field"),
+            Error::FrameGap { expected, got } => {
+                write!(f, "expected frame {expected}, got {got}")
+            }
+            Error::Map(err) => write!(f, "invalid map: {err}"),
+            Error::Rules(err) => write!(f, "invalid food rules: {err}"),
+            Error::Replay(err) => write!(f, "invalid replay: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<DecodeError> for Error {
+    fn from(err: DecodeError) -> Self {
+        Error::Decode(err)
+    }
+}
+
+impl From<MapError> for Error {
+    fn from(err: MapError) -> Self {
+        Error::Map(err)
+    }
+}
+
+impl From<RulesError> for Error {
+    fn from(err: RulesError) -> Self {
+        Error::Rules(err)
+    }
+}
+
+impl From<ReplayError> for Error {
+    fn from(err: ReplayError) -> Self {
+        Error::Replay(err)
+    }
+}
+
+/// Shorthand for an `InvalidConfig` error
+pub fn invalid<T>(message: impl Into<String>) -> Result<T, Error> {
+    Err(Error::InvalidConfig(message.into()))
+}
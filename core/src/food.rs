@@ -1,8 +1,18 @@
-use rand::{seq::SliceRandom, Rng};
+use std::{collections::HashMap, fmt};
 
-use crate::types::{Field, FieldPoint, Food, FoodType};
+use bincode::{
+    de::Decoder,
+    enc::Encoder,
+    error::{DecodeError, EncodeError},
+    Decode, Encode,
+};
 
-const BAG: [FoodType; 6] = [
+use crate::{
+    rng::Rng,
+    types::{Field, FieldPoint, Food, FoodType},
+};
+
+pub const BAG: [FoodType; 6] = [
     FoodType::Whale,
     FoodType::Turtle,
     FoodType::Chameleon,
@@ -11,134 +21,285 @@ const BAG: [FoodType; 6] = [
     FoodType::Caterpillar,
 ];
 
-#[derive(bincode::Encode, bincode::Decode, PartialEq, Debug, Clone)]
+/// How much food there is, how it scores and how long specials last. Amounts are field cells,
+/// a special takes two of them
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoodRules {
+    pub basic_weight: u8,
+    pub special_weight: u8,
+    /// Cells a special takes, side by side on a row
+    pub special_size: u8,
+    /// Ticks a special stays on the field, 0 keeps it until eaten
+    pub special_ticks: u8,
+    /// A special follows every that many basic foods, 0 means no specials
+    pub special_every: u16,
+    /// Chance of each special in `BAG` order, 0 leaves it out
+    pub bag: [u8; BAG.len()],
+    /// Food kept on the field for each snake
+    pub per_snake: u16,
+    /// Food on the field never goes over it
+    pub max: u16,
+}
+
+impl FoodRules {
+    pub const DEFAULT: FoodRules = FoodRules {
+        basic_weight: 8,
+        special_weight: 45,
+        special_size: 2,
+        special_ticks: 30,
+        special_every: 4,
+        bag: [1; BAG.len()],
+        per_snake: 1,
+        max: u16::MAX,
+    };
+
+    /// Draws a special from the bag, as likely as its chance over the sum of all of them
+    pub fn pick_special(&self, rng: &mut Rng) -> Option<FoodType> {
+        let total: u16 = self.bag.iter().map(|chance| *chance as u16).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut n = rng.gen_range(0..total);
+        for (shape, chance) in BAG.iter().zip(self.bag.iter()) {
+            if n < *chance as u16 {
+                return Some(*shape);
+            }
+            n -= *chance as u16;
+        }
+        None
+    }
+
+    /// One `key: value` per line, `#` starts a comment. Keys left out keep their default
+    ///
+    /// ```text
+    /// basic_weight: 8
+    /// special_weight: 45
+    /// special_size: 2
+    /// special_ticks: 30
+    /// special_every: 4
+    /// bag: whale 1, turtle 1, chameleon 1, elephant 1, alien 1, caterpillar 1
+    /// per_snake: 1
+    /// max: 40
+    /// ```
+    pub fn parse(text: &str) -> Result<FoodRules, RulesError> {
+        let mut rules = FoodRules::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| RulesError {
+                line: n + 1,
+                message,
+            };
+            let Some((key, value)) = line.split_once(':') else {
+                return Err(error("expected 'key: value'".to_owned()));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            let number = || format!("{key} must be a number");
+            match key {
+                "basic_weight" => {
+                    rules.basic_weight = value.parse().map_err(|_| error(number()))?
+                }
+                "special_weight" => {
+                    rules.special_weight = value.parse().map_err(|_| error(number()))?
+                }
+                "special_size" => {
+                    let size = value.parse().ok().filter(|size| *size > 0);
+                    rules.special_size =
+                        size.ok_or_else(|| error(format!("{key} must be 1 or more")))?
+                }
+                "special_ticks" => {
+                    rules.special_ticks = value.parse().map_err(|_| error(number()))?
+                }
+                "special_every" => {
+                    rules.special_every = value.parse().map_err(|_| error(number()))?
+                }
+                "per_snake" => rules.per_snake = value.parse().map_err(|_| error(number()))?,
+                "max" => rules.max = value.parse().map_err(|_| error(number()))?,
+                "bag" => rules.bag = parse_bag(value).map_err(error)?,
+                _ => return Err(error(format!("unknown key '{key}'"))),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+impl Default for FoodRules {
+    fn default() -> Self {
+        FoodRules::DEFAULT
+    }
+}
+
+/// Specials missing from the list are left out of the bag
+fn parse_bag(value: &str) -> Result<[u8; BAG.len()], String> {
+    let mut bag = [0; BAG.len()];
+    for entry in value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (name, chance) = entry.split_once(' ').unwrap_or((entry, "1"));
+        let Some(i) = BAG
+            .iter()
+            .position(|shape| format!("{shape:?}").eq_ignore_ascii_case(name))
+        else {
+            return Err(format!("unknown special '{name}'"));
+        };
+        bag[i] = chance
+            .trim()
+            .parse()
+            .map_err(|_| format!("chance of {name} must be a number"))?;
+    }
+    Ok(bag)
+}
+
+/// 1-based line of a broken rules file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RulesError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Foods on the field, along with the index of the food on each cell they take. The index is
+/// rebuilt on decode rather than sent around
+#[derive(PartialEq, Debug, Clone, Default)]
 pub struct FoodField {
     pub foods: Vec<Food>,
     pub minimum: u16,
     pub count: u16,
-    bag: [FoodType; BAG.len()],
+    cells: HashMap<FieldPoint, usize>,
 }
-impl Default for FoodField {
-    fn default() -> Self {
-        FoodField {
-            foods: Vec::new(),
-            count: 0,
-            minimum: 0,
-            bag: BAG,
-        }
+
+impl Encode for FoodField {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        self.foods.encode(encoder)?;
+        self.minimum.encode(encoder)?;
+        self.count.encode(encoder)
+    }
+}
+
+impl Decode for FoodField {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let mut food = FoodField {
+            foods: Vec::decode(decoder)?,
+            minimum: u16::decode(decoder)?,
+            count: u16::decode(decoder)?,
+            cells: HashMap::new(),
+        };
+        food.reindex();
+        Ok(food)
     }
 }
+bincode::impl_borrow_decode!(FoodField);
+
 impl FoodField {
     pub fn total_filled(&self) -> u16 {
         let mut total: u16 = 0;
         for f in self.foods.iter() {
-            total += f.size as u16;
+            total = total.saturating_add(f.size as u16);
         }
         total
     }
     pub fn has_at(&self, p: &FieldPoint) -> Option<usize> {
-        (0..self.foods.len()).find(|&i| self.foods[i].is_at(p))
+        self.cells.get(p).copied()
     }
 
+    /// The last food takes the place of the grabbed one
     pub fn grab(&mut self, p: &FieldPoint) -> Option<Food> {
-        let i = self.has_at(p);
-        if let Some(i) = i {
-            let food = self.foods.remove(i);
-            return Some(food);
+        let i = self.has_at(p)?;
+        let food = self.foods.swap_remove(i);
+        for cell in food.cells() {
+            self.cells.remove(&cell);
         }
-        None
+        if let Some(moved) = self.foods.get(i) {
+            for cell in moved.cells() {
+                self.cells.insert(cell, i);
+            }
+        }
+        Some(food)
     }
 
     pub fn set_food(&mut self, food: Food) {
+        for cell in food.cells() {
+            self.cells.insert(cell, self.foods.len());
+        }
         self.foods.push(food);
-        self.count += 1;
+        self.count = self.count.wrapping_add(1);
     }
 
-    pub fn add_food(&mut self, field: &Field) {
-        let total_filled = self.total_filled();
-        if total_filled >= self.minimum {
-            return;
-        }
-        let max = (field.bit_set.len() - field.bit_set.count_ones(..)) as u16 - total_filled;
-        if max < 1 {
-            return;
-        }
-
-        // @todo clear-me, way too complex
-        let mut nth = rand::thread_rng().gen_range(0..max);
-        let mut idx: u16 = 0;
+    /// Leaves only the foods `keep` says so
+    pub fn retain(&mut self, keep: impl FnMut(&Food) -> bool) {
+        self.foods.retain(keep);
+        self.reindex();
+    }
 
-        // finds the nth free position
-        let max_len = field.bit_set.len() as u16;
-        loop {
-            if nth == 0 || idx == max_len {
-                break;
+    fn reindex(&mut self) {
+        self.cells.clear();
+        for (i, food) in self.foods.iter().enumerate() {
+            for cell in food.cells() {
+                self.cells.insert(cell, i);
             }
-            if !field.idx_filled(idx) {
-                nth -= 1;
-            }
-            idx += 1;
-        }
-
-        // won't overlap with current food
-        while idx < max_len && self.has_at(&field.from_idx(idx)).is_some() || field.idx_filled(idx)
-        {
-            idx = (idx + 1) % field.bit_set.len() as u16;
         }
+    }
 
-        if idx == max_len {
+    /// Walks the free cells from a random one, at most past every cell taken by food
+    pub fn add_food(&mut self, field: &Field, rng: &mut Rng, rules: &FoodRules) {
+        let total_filled = self.total_filled();
+        if total_filled >= self.minimum || total_filled >= rules.max {
             return;
         }
+        let Some(p) = self.find_spot(field, rng, 1) else {
+            return;
+        };
 
-        let food = Food::new(FoodType::Basic, field.from_idx(idx));
+        let food = Food::new(FoodType::Basic, p, rules);
         self.set_food(food);
-        self.count += 1;
-        if self.count % 5 == 0 {
-            let food = self.random_special(max - 1, field);
+        // specials are counted too, so each one comes after `special_every` basic foods
+        let every = rules.special_every;
+        let room = total_filled as u32 + 1 + rules.special_size as u32 <= rules.max as u32;
+        if every > 0 && room && self.count as u32 % (every as u32 + 1) == every as u32 {
+            let food = self.random_special(field, rng, rules);
             if let Some(food) = food {
                 self.set_food(food);
             }
         }
     }
 
-    /// Special foods requires 2 slots on the field. It must be not placed on the last col,
-    ///  as it can't wrap to the next row.
-    pub fn random_special(&mut self, max: u16, field: &Field) -> Option<Food> {
-        let required = (self.foods.len() * 2) as u16;
-        // check enought space
-        let max = max / 2;
-        if max < 1 || required >= max - 1 {
-            return None;
-        }
-        let max = max - required - 1;
-        let mut rng = rand::thread_rng();
-        let mut nth = rng.gen_range(0..max);
-
-        // make sure each idx doesn't land on the last col
-        let mut idx: u16 = field.width % 2;
-        while nth > 0 && (idx as usize + 1) < field.bit_set.len() {
-            let p1 = field.from_idx(idx);
-            let p2 = FieldPoint {
-                x: p1.x + 1,
-                y: p1.y,
-            };
-            if !field.filled(&p1) && !field.filled(&p2) {
-                nth -= 1;
-            }
-            idx += 2;
-        }
+    pub fn random_special(
+        &mut self,
+        field: &Field,
+        rng: &mut Rng,
+        rules: &FoodRules,
+    ) -> Option<Food> {
+        let p = self.find_spot(field, rng, rules.special_size)?;
+        let shape = rules.pick_special(rng)?;
+        Some(Food::new(shape, p, rules))
+    }
 
-        // possibly well filled field. Skip then
-        if idx as usize >= field.bit_set.len() {
-            return None;
-        }
+    /// Where a food `size` cells wide fits, starting from a random free cell. Only `None` when
+    /// there's no room for it anywhere
+    pub fn find_spot(&self, field: &Field, rng: &mut Rng, size: u8) -> Option<FieldPoint> {
+        field.free_cells(rng).find(|p| self.fits(field, p, size))
+    }
 
-        self.bag.shuffle(&mut rng);
-        let food = Food::new(self.bag[0], field.from_idx(idx));
-        Some(food)
+    /// Footprints stay on the row they start, as food doesn't wrap around the field. Every
+    /// cell must be clear of snakes, walls, portals and other food
+    pub fn fits(&self, field: &Field, p: &FieldPoint, size: u8) -> bool {
+        (0..size as u32).all(|dx| {
+            let cell = p.add_tuple((dx, 0));
+            cell.x < field.width && !field.filled(&cell) && self.has_at(&cell).is_none()
+        })
     }
 
     pub fn tick(&mut self) {
+        let len = self.foods.len();
         self.foods.retain_mut(|food| {
             if food.ticks_left == 1 {
                 return false;
@@ -148,5 +309,120 @@ impl FoodField {
             }
             true
         });
+        if self.foods.len() != len {
+            self.reindex();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// A field with random snake cells, walls and a portal pair
+    fn random_field(width: u32, height: u32, rng: &mut Rng) -> Field {
+        let mut field = Field::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let p = FieldPoint { x, y };
+                match rng.next_u64() % 6 {
+                    0 => field.set(&p, true),
+                    1 => field.set_wall(&p, true),
+                    _ => {}
+                }
+            }
+        }
+        let free: Vec<FieldPoint> = field.free_cells(rng).take(2).collect();
+        if let [a, b] = free[..] {
+            field.add_wormhole((a, b));
+        }
+        field
+    }
+
+    /// Every food cell is on the field, clear of anything filled, taken by one food only and
+    /// indexed to it
+    fn assert_apart(field: &Field, food: &FoodField) {
+        let mut taken = HashSet::new();
+        for (i, f) in food.foods.iter().enumerate() {
+            assert!(
+                f.location.x + f.size as u32 <= field.width,
+                "{f:?} straddles a row"
+            );
+            for cell in f.cells() {
+                assert!(cell.y < field.height);
+                assert!(!field.filled(&cell), "{f:?} on something filled");
+                assert!(taken.insert(cell), "{f:?} overlaps other food");
+                assert_eq!(food.has_at(&cell), Some(i));
+            }
+        }
+    }
+
+    #[test]
+    fn food_never_overlaps() {
+        let rules = FoodRules {
+            special_every: 1,
+            special_size: 3,
+            ..FoodRules::DEFAULT
+        };
+        for seed in 0..50 {
+            let mut rng = Rng::new(seed);
+            let width = 1 + (rng.next_u64() % 40) as u32;
+            let height = 1 + (rng.next_u64() % 40) as u32;
+            let field = random_field(width, height, &mut rng);
+            let mut food = FoodField {
+                minimum: u16::MAX,
+                ..FoodField::default()
+            };
+            for round in 0..200 {
+                food.add_food(&field, &mut rng, &rules);
+                // eating some of it moves foods around the index
+                if round % 3 == 0 {
+                    if let Some(f) = food.foods.first().copied() {
+                        food.grab(&f.location);
+                    }
+                }
+                assert_apart(&field, &food);
+            }
+        }
+    }
+
+    /// Every field up to 5x4, odd and even, filled with multi-cell food until none fits
+    #[test]
+    fn small_fields_fill_up_without_straddling() {
+        for width in 1..=5 {
+            for height in 1..=4 {
+                for seed in 0..8 {
+                    let mut rng = Rng::new(seed);
+                    let field = match seed {
+                        0 => Field::new(width, height),
+                        _ => random_field(width, height, &mut rng),
+                    };
+                    for special_size in 2..=3 {
+                        let rules = FoodRules {
+                            special_size,
+                            ..FoodRules::DEFAULT
+                        };
+                        let mut food = FoodField::default();
+                        while let Some(special) = food.random_special(&field, &mut rng, &rules) {
+                            food.set_food(special);
+                            assert_apart(&field, &food);
+                        }
+                        // nothing was left out
+                        let cells = (0..field.len()).map(|idx| field.from_idx(idx));
+                        assert!(cells.clone().all(|p| !food.fits(&field, &p, special_size)));
+                        // single cells still go wherever is left
+                        while let Some(p) = food.find_spot(&field, &mut rng, 1) {
+                            food.set_food(Food::new(FoodType::Basic, p, &rules));
+                        }
+                        assert_apart(&field, &food);
+                        assert!(cells
+                            .into_iter()
+                            .all(|p| field.filled(&p) || food.has_at(&p).is_some()));
+                    }
+                }
+            }
+        }
     }
 }
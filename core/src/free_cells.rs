@@ -0,0 +1,114 @@
+use fixedbitset::FixedBitSet;
+
+/// Indexes of the empty cells of a field, as a bitset along with their count. Picking the nth
+/// one goes by rank, so it only depends on which cells are free and not on how they got there,
+/// and a game rebuilt from its data picks the same cells
+#[derive(Debug, Clone)]
+pub struct FreeCells {
+    free: FixedBitSet,
+    len: usize,
+}
+
+impl FreeCells {
+    /// Every cell starts free
+    pub fn new(len: usize) -> FreeCells {
+        let mut free = FixedBitSet::with_capacity(len);
+        free.insert_range(..);
+        FreeCells { free, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.free.contains(idx)
+    }
+
+    /// The free cell with `n` free cells before it
+    pub fn nth(&self, n: usize) -> usize {
+        let mut n = n as u32;
+        for (block, bits) in self.free.as_slice().iter().enumerate() {
+            let ones = bits.count_ones();
+            if n >= ones {
+                n -= ones;
+                continue;
+            }
+            let mut bits = *bits;
+            for _ in 0..n {
+                bits &= bits - 1;
+            }
+            return block * u32::BITS as usize + bits.trailing_zeros() as usize;
+        }
+        panic!("no free cell {n} out of {}", self.len);
+    }
+
+    /// Every free cell once in index order, starting from the `n`th one and going around
+    pub fn from_nth(&self, n: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = match self.is_empty() {
+            true => 0,
+            false => self.nth(n % self.len),
+        };
+        let after = self.free.ones().skip_while(move |idx| *idx < start);
+        after.chain(self.free.ones().take_while(move |idx| *idx < start))
+    }
+
+    pub fn insert(&mut self, idx: usize) {
+        if !self.free.put(idx) {
+            self.len += 1;
+        }
+    }
+
+    pub fn remove(&mut self, idx: usize) {
+        if self.free.contains(idx) {
+            self.free.set(idx, false);
+            self.len -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::FreeCells;
+    use crate::rng::Rng;
+
+    /// Random inserts and removes keep the cells in line with a plain set, listed in order
+    #[test]
+    fn matches_a_set() {
+        const LEN: usize = 64;
+        for seed in 0..32 {
+            let mut rng = Rng::new(seed);
+            let mut cells = FreeCells::new(LEN);
+            let mut expected: BTreeSet<usize> = (0..LEN).collect();
+            for _ in 0..500 {
+                let idx = (rng.next_u64() % LEN as u64) as usize;
+                match rng.next_u64() % 2 {
+                    0 => {
+                        cells.insert(idx);
+                        expected.insert(idx);
+                    }
+                    _ => {
+                        cells.remove(idx);
+                        expected.remove(&idx);
+                    }
+                }
+                assert_eq!(cells.len(), expected.len());
+                let listed: Vec<usize> = (0..cells.len()).map(|n| cells.nth(n)).collect();
+                assert_eq!(listed, expected.iter().copied().collect::<Vec<_>>());
+                if let Some(n) = (!expected.is_empty()).then(|| idx % expected.len()) {
+                    let around: Vec<usize> = cells.from_nth(n).collect();
+                    let rotated: Vec<usize> =
+                        listed[n..].iter().chain(&listed[..n]).copied().collect();
+                    assert_eq!(around, rotated);
+                }
+                assert!((0..LEN).all(|idx| cells.contains(idx) == expected.contains(&idx)));
+            }
+        }
+    }
+}
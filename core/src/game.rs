@@ -1,19 +1,29 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::{
+    effect::EffectKind,
+    error::{invalid, Error},
     food::FoodField,
+    map::Spawn,
     render::GameRender,
-    snake::{Snake, SnakeNode},
-    types::{Direction, Field, GameConfig, GameState},
+    replay::{Recorder, ReplayData, ReplayEvent},
+    rng::Rng,
+    snake::{Snake, SnakeNode, SnakeState},
+    spawn::SpawnPlanner,
+    types::{Cell, Direction, Field, FieldPoint, FoodContest, GameConfig, GameState, Wormhole},
     utils::{decode, encode},
 };
 
-#[derive(bincode::Encode, bincode::Decode, Debug)]
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
 pub struct GameData {
     pub config: GameConfig,
-    pub snakes: HashMap<u16, Snake>,
+    pub snakes: BTreeMap<u16, Snake>,
     pub food: FoodField,
     pub state: GameState,
+    pub rng: Rng,
+    pub walls: Vec<FieldPoint>,
+    pub wormholes: Vec<Wormhole>,
+    pub next_id: u16,
 }
 
 impl GameData {
@@ -23,30 +33,109 @@ impl GameData {
             snakes: game.snakes.clone(),
             food: game.food.clone(),
             state: game.state.clone(),
+            rng: game.rng.clone(),
+            walls: game.field.walls(),
+            wormholes: game.field.wormholes.clone(),
+            next_id: game.next_id,
         }
     }
+
+    /// Anything out of the field would make the game panic later on
+    pub fn validate(&self) -> Result<(), Error> {
+        let config = &self.config;
+        config.validate()?;
+        let (width, height) = config.dim;
+        for (id, snake) in self.snakes.iter() {
+            if *id != snake.id {
+                return invalid(format!("snake {} listed as {id}", snake.id));
+            }
+            let max = snake.direction.max;
+            if (max.x, max.y) != config.dim {
+                return invalid(format!("snake {} moves on another field", snake.id));
+            }
+            if snake.is_alive() && snake.nodes.is_empty() {
+                return invalid(format!("snake {} is alive without a body", snake.id));
+            }
+            if !snake
+                .nodes
+                .iter()
+                .all(|node| config.contains(&node.position))
+            {
+                return invalid(format!("snake {} out of the field", snake.id));
+            }
+        }
+        for food in self.food.foods.iter() {
+            let end = food.location.x as u64 + food.size as u64;
+            if food.size == 0 || end > width as u64 || food.location.y >= height {
+                return invalid(format!("food out of the field at {:?}", food.location));
+            }
+        }
+        let wormholes = self.wormholes.iter().flat_map(|(a, b)| [a, b]);
+        if !self
+            .walls
+            .iter()
+            .chain(wormholes)
+            .all(|p| config.contains(p))
+        {
+            return invalid(format!(
+                "walls or wormholes out of the {width}x{height} field"
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub struct Game {
     pub config: GameConfig,
-    pub snakes: HashMap<u16, Snake>,
+    pub snakes: BTreeMap<u16, Snake>,
     pub field: Field,
     pub food: FoodField,
     pub state: GameState,
+    pub rng: Rng,
+    pub recorder: Option<Recorder>,
+    /// Next id handed out to a snake, ids of snakes that left are never reused
+    pub next_id: u16,
 }
 
 impl Game {
     pub fn new(config: GameConfig) -> Game {
         let (width, height) = config.dim;
-        let field = Field::new(width, height);
+        let mut field = Field::new(width, height);
+        if let Some(map) = &config.map {
+            for wall in map.walls.iter() {
+                field.set_wall(wall, true);
+            }
+            for wormhole in map.wormholes.iter() {
+                field.add_wormhole(*wormhole);
+            }
+        }
+        let rng = Rng::new(config.seed);
 
         Game {
             config,
+            rng,
             food: FoodField::default(),
-            snakes: HashMap::new(),
+            snakes: BTreeMap::new(),
             field,
             state: GameState::None,
+            recorder: None,
+            next_id: 1,
+        }
+    }
+
+    /// Records every event from now on, along with a snapshot of the current state
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(Recorder::new(self));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<ReplayData> {
+        self.recorder.take().map(Recorder::finish)
+    }
+
+    fn record(&mut self, event: ReplayEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.push(event);
         }
     }
 
@@ -54,12 +143,20 @@ impl Game {
         encode(GameData::from_game(self)).unwrap()
     }
 
-    pub fn set_game_data(&mut self, data: Vec<u8>) {
-        let (data, _size): (GameData, usize) = decode(&data).unwrap();
+    /// Nothing changes when the bytes don't decode into a valid game
+    pub fn set_game_data(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        let (data, _size): (GameData, usize) = decode(&data)?;
+        data.validate()?;
 
         let (width, height) = data.config.dim;
         let mut field = Field::new(width, height);
-        for (_id, snake) in data.snakes.iter() {
+        for wall in data.walls.iter() {
+            field.set_wall(wall, true);
+        }
+        for wormhole in data.wormholes.iter() {
+            field.add_wormhole(*wormhole);
+        }
+        for snake in data.snakes.values().filter(|s| s.is_alive()) {
             for node in snake.nodes.iter() {
                 field.set(&node.position, true);
             }
@@ -69,62 +166,290 @@ impl Game {
         self.snakes = data.snakes;
         self.field = field;
         self.state = data.state;
+        self.rng = data.rng;
+        self.next_id = data.next_id;
+        Ok(())
     }
 
-    // @todo spot an empty continuous space to fit the snake
-    pub fn add_snake(&mut self) -> u16 {
+    /// Hatches a new snake where there's room for it, see `SpawnPlanner`
+    pub fn add_snake(&mut self) -> Result<u16, Error> {
+        let id = self.allocate_id();
+        self.hatch_snake(id, self.snakes.len())?;
+        self.next_id = id.wrapping_add(1);
+        self.food.minimum = self.food.minimum.saturating_add(self.config.food.per_snake);
+        self.record(ReplayEvent::Join(id));
+        Ok(id)
+    }
+
+    /// Counts up from `next_id`, skipping 0 and ids still in use once the counter wraps around
+    fn allocate_id(&self) -> u16 {
+        let mut id = self.next_id;
+        while id == 0 || self.snakes.contains_key(&id) {
+            id = id.wrapping_add(1);
+        }
+        id
+    }
+
+    /// Map spawn points are taken in turns, otherwise the configured start goes first
+    fn hatch_snake(&mut self, id: u16, slot: usize) -> Result<(), Error> {
+        let mut preferred: Vec<Spawn> = match &self.config.map {
+            Some(map) => map.spawns.clone(),
+            None => Vec::new(),
+        };
+        if preferred.is_empty() {
+            let (x, y) = self.config.start;
+            preferred.push(Spawn {
+                position: FieldPoint { x, y },
+                direction: self.config.direction,
+            });
+        }
+        let len = preferred.len();
+        preferred.rotate_left(slot % len);
+
+        let planner = SpawnPlanner {
+            field: &self.field,
+            food: &self.food,
+            size: self.config.size,
+            wrap: self.config.wrap,
+            heads: self
+                .snakes
+                .values()
+                .filter(|snake| snake.is_alive())
+                .filter_map(|snake| snake.nodes.back().map(|node| node.position))
+                .collect(),
+        };
+        let spawn = planner.plan(&preferred, &mut self.rng)?;
+
         let mut config = self.config.clone();
-        config.start = (0, self.snakes.len() as u16);
-        self.food.minimum += 1;
-        let id = (self.snakes.len() + 1) as u16;
+        config.start = (spawn.position.x, spawn.position.y);
+        config.direction = spawn.direction;
         let snake = Snake::new(&mut self.field, &config, id);
         self.snakes.insert(id, snake);
-        id
+        Ok(())
+    }
+
+    /// A new game on the same config with another seed, the current players hatched in it
+    /// under the same ids
+    pub fn rematch(&self, seed: u64) -> Game {
+        let config = GameConfig {
+            seed,
+            ..self.config.clone()
+        };
+        let mut game = Game::new(config);
+        game.next_id = self.next_id;
+        game.food.minimum = self.food.minimum;
+        for (slot, (id, snake)) in self.snakes.iter().enumerate() {
+            // when there's no room left the snake sits this game out
+            if game.hatch_snake(*id, slot).is_err() {
+                let mut snake = snake.clone();
+                snake.state = SnakeState::Dead;
+                snake.nodes.clear();
+                snake.score = 0;
+                snake.effects.clear();
+                game.snakes.insert(*id, snake);
+            }
+        }
+        game
     }
 
+    /// Replaces the obstacle layout. Meant to be set up before snakes join
+    pub fn set_walls(&mut self, walls: &[FieldPoint]) {
+        self.field.clear_walls();
+        for wall in walls.iter() {
+            self.field.set_wall(wall, true);
+        }
+    }
+
+    pub fn set_wormholes(&mut self, wormholes: &[Wormhole]) {
+        self.field.clear_wormholes();
+        for wormhole in wormholes.iter() {
+            self.field.add_wormhole(*wormhole);
+        }
+    }
+
+    /// Unknown ids are left alone, they aren't recorded either
     pub fn remove_snake(&mut self, snake_id: u16) {
         //@todo clear rendering coming soon..
-        if let Some(snake) = self.snakes.remove(&snake_id) {
-            self.food.minimum -= 1;
-            for node in snake.nodes.iter() {
-                self.field.set(&node.position, false);
+        if let Some(mut snake) = self.snakes.remove(&snake_id) {
+            self.record(ReplayEvent::Leave(snake_id));
+            self.food.minimum = self.food.minimum.saturating_sub(self.config.food.per_snake);
+            if snake.is_alive() {
+                snake.die(&mut self.field);
             }
         }
     }
 
-    pub fn add_food(&mut self) {
-        self.food.add_food(&self.field);
+    /// Moves every snake at once: next heads are computed from the same snapshot and conflicts
+    /// are resolved together, so the outcome doesn't depend on the order snakes are stored.
+    fn crawl(&mut self) {
+        let mut heads: BTreeMap<u16, SnakeNode> = BTreeMap::new();
+        let mut dead: Vec<u16> = Vec::new();
+        // slowed down snakes sit still on the beat
+        let movers = self
+            .snakes
+            .values()
+            .filter(|snake| snake.is_alive() && !snake.effect_on_beat(EffectKind::Slow));
+        for snake in movers {
+            match snake.next_head_through(&self.field.wormholes) {
+                Some(head) => {
+                    heads.insert(snake.id, head);
+                }
+                // hit a solid edge
+                None => dead.push(snake.id),
+            }
+        }
+
+        let eaters = self.resolve_food(&heads);
+
+        // tails of snakes that won't grow are free to be taken on this same tick
+        let vacated: Vec<FieldPoint> = heads
+            .keys()
+            .flat_map(|id| {
+                let snake = &self.snakes[id];
+                let drops = Game::tail_drops(snake, eaters.contains(id));
+                snake.nodes.iter().take(drops).map(|node| node.position)
+            })
+            .collect();
+
+        dead.extend(
+            heads
+                .iter()
+                .filter(|(id, head)| {
+                    let p = head.position;
+                    let blocked = self.field.filled(&p)
+                        && !vacated.contains(&p)
+                        && !self.passes_through(&self.snakes[id], &p);
+                    let head_to_head = heads.values().filter(|h| h.position.eq(&p)).count() > 1;
+                    blocked || head_to_head
+                })
+                .map(|(id, _head)| *id),
+        );
+
+        for id in dead.iter() {
+            self.snakes.get_mut(id).unwrap().die(&mut self.field);
+        }
+
+        let movers: Vec<(u16, SnakeNode)> = heads
+            .into_iter()
+            .filter(|(id, _head)| !dead.contains(id))
+            .collect();
+        for (id, mut head) in movers.iter().copied() {
+            let eats = eaters.contains(&id);
+            let snake = self.snakes.get_mut(&id).unwrap();
+            let drops = Game::tail_drops(snake, eats);
+            if eats {
+                let food = self.food.grab(&head.position).unwrap();
+                head.stuffed = true;
+                let multiplier = match snake.has_effect(EffectKind::Multiplier) {
+                    true => 2,
+                    false => 1,
+                };
+                snake.score = snake.score.saturating_add(food.weight as u16 * multiplier);
+                if let Some(kind) = EffectKind::of(food.shape) {
+                    snake.add_effect(kind);
+                }
+            }
+            for _ in 0..drops {
+                let tail = snake.nodes.pop_front().unwrap();
+                self.field.set(&tail.position, false);
+            }
+            snake.nodes.push_back(head);
+        }
+        for (_id, head) in movers.iter() {
+            // leftovers of contested food are spoiled
+            self.food.grab(&head.position);
+            self.field.set(&head.position, true);
+        }
+        self.sync_snake_cells();
+
+        self.check_over();
     }
 
-    fn crawl(&mut self) {
-        for snake in self.snakes.values_mut() {
-            let mut next_head = snake.next_head();
-            let SnakeNode { position: p, .. } = next_head;
-            if self.field.filled(&p) {
-                // self.state = GameState::Over; //@todo comming soon...
+    /// Tail nodes a moving snake lets go of: none when eating or growing, one more when
+    /// shrinking, always leaving the snake with its head and one node
+    fn tail_drops(snake: &Snake, eats: bool) -> usize {
+        let mut drops = match eats || snake.effect_on_beat(EffectKind::Grow) {
+            true => 0,
+            false => 1,
+        };
+        if snake.effect_on_beat(EffectKind::Shrink) {
+            drops += 1;
+        }
+        drops.min(snake.nodes.len().saturating_sub(1))
+    }
+
+    /// Ghosts go through other bodies, but never through walls or themselves
+    fn passes_through(&self, snake: &Snake, p: &FieldPoint) -> bool {
+        snake.has_effect(EffectKind::Ghost)
+            && self.field.cell(p) == Cell::Snake
+            && !snake.nodes.iter().any(|node| node.position.eq(p))
+    }
+
+    /// Bodies sharing cells with a ghost get their occupancy cleared when either moves away,
+    /// so the snake cells are rebuilt whenever they don't add up
+    fn sync_snake_cells(&mut self) {
+        let alive = self.snakes.values().filter(|snake| snake.is_alive());
+        let nodes: usize = alive.clone().map(|snake| snake.nodes.len()).sum();
+        if nodes as u64 == self.field.snake_count() {
+            return;
+        }
+        self.field.clear_snakes();
+        for snake in alive {
+            for node in snake.nodes.iter() {
+                self.field.set(&node.position, true);
+            }
+        }
+    }
+
+    /// Picks which snakes eat this tick. Food reached by more than one head goes to the longest
+    /// snake or to nobody, as set by `GameConfig::food_contest`; a tie always goes to nobody.
+    fn resolve_food(&self, heads: &BTreeMap<u16, SnakeNode>) -> Vec<u16> {
+        let mut contenders: BTreeMap<usize, Vec<u16>> = BTreeMap::new();
+        for (id, head) in heads.iter() {
+            if let Some(i) = self.food.has_at(&head.position) {
+                contenders.entry(i).or_default().push(*id);
+            }
+        }
+
+        let mut eaters = Vec::new();
+        for ids in contenders.into_values() {
+            if ids.len() == 1 {
+                eaters.push(ids[0]);
                 continue;
             }
-            let nodes = &mut snake.nodes;
-            self.field.set(&p, true);
-            match self.food.grab(&p) {
-                Some(food) => {
-                    next_head.stuffed = true;
-                    snake.score += food.weight as u16;
-                }
-                None => {
-                    let tail = nodes.pop_front().unwrap();
-                    self.field.set(&tail.position, false);
-                }
+            if self.config.food_contest == FoodContest::Nobody {
+                continue;
             }
-            nodes.push_back(next_head);
+            let len = |id: &u16| self.snakes[id].nodes.len();
+            let longest = ids.iter().map(len).max().unwrap();
+            let winners: Vec<&u16> = ids.iter().filter(|id| len(id) == longest).collect();
+            if winners.len() == 1 {
+                eaters.push(*winners[0]);
+            }
+        }
+        eaters
+    }
+
+    /// Single player games are over when the snake dies, multiplayer when one or none survives
+    fn check_over(&mut self) {
+        let alive = self.snakes.values().filter(|s| s.is_alive()).count();
+        let over = match self.snakes.len() {
+            0 => false,
+            1 => alive == 0,
+            _ => alive <= 1,
+        };
+        if over {
+            self.state = GameState::Over;
         }
     }
 
-    pub fn add_missing_food(&mut self) {
-        self.food.add_food(&self.field);
+    fn add_missing_food(&mut self) {
+        self.food
+            .add_food(&self.field, &mut self.rng, &self.config.food);
     }
 
     pub fn head_to(&mut self, snake_id: u16, to: Direction) {
+        self.record(ReplayEvent::Turn(snake_id, to));
         if let Some(snake) = self.snakes.get_mut(&snake_id) {
             snake.head_to(to);
         }
@@ -132,19 +457,270 @@ impl Game {
 
     pub fn draw(&mut self, render: &mut impl GameRender) {
         // @todo use snake_id as render param
-        for (_id, snake) in self.snakes.iter() {
+        for snake in self.snakes.values().filter(|s| s.is_alive()) {
             render.snake(snake, &self.food);
         }
+        for wall in self.field.walls() {
+            render.wall(&wall);
+        }
+        for (a, b) in self.field.wormholes.iter() {
+            render.portal(a);
+            render.portal(b);
+        }
         for food in self.food.foods.iter() {
             render.food(food);
         }
     }
 
     pub fn tick(&mut self) {
-        if self.state == GameState::Quit {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.tick();
+        }
+        if self.state == GameState::Quit || self.state == GameState::Over {
             return;
         }
         self.food.tick();
         self.crawl();
+        for snake in self.snakes.values_mut() {
+            snake.tick_effects();
+        }
+        self.add_missing_food();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Food, FoodType};
+
+    fn game() -> Game {
+        Game::new(GameConfig {
+            dim: (60, 40),
+            ..GameConfig::default()
+        })
+    }
+
+    /// Same seed and same turns, with snakes that joined and left first. The second game picks
+    /// up from the first one's data, as a replay does
+    #[test]
+    fn same_seed_and_turns_play_the_same() {
+        for seed in 0..10 {
+            let mut live = Game::new(GameConfig {
+                seed,
+                ..GameConfig::default()
+            });
+            for _ in 0..5 {
+                live.add_snake().unwrap();
+            }
+            live.remove_snake(2);
+            live.remove_snake(4);
+            live.state = GameState::Playing;
+            let mut resumed = Game::new(live.config.clone());
+            resumed.set_game_data(live.encode_game_data()).unwrap();
+            for tick in 0..60u16 {
+                for game in [&mut live, &mut resumed] {
+                    if tick % 9 == 0 {
+                        let to = [Direction::Up, Direction::Right][tick as usize % 2];
+                        game.head_to(1 + tick % 5, to);
+                    }
+                    game.tick();
+                }
+                assert_eq!(
+                    live.encode_game_data(),
+                    resumed.encode_game_data(),
+                    "seed {seed}, tick {tick}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ids_are_not_reused() {
+        let mut game = game();
+        let a = game.add_snake().unwrap();
+        let b = game.add_snake().unwrap();
+        game.remove_snake(b);
+        let c = game.add_snake().unwrap();
+        assert_eq!((a, b, c), (1, 2, 3));
+        game.remove_snake(a);
+        assert_eq!(game.add_snake().unwrap(), 4);
+    }
+
+    #[test]
+    fn wrapping_ids_skip_live_ones() {
+        let mut game = game();
+        let first = game.add_snake().unwrap();
+        game.next_id = u16::MAX;
+        assert_eq!(game.add_snake().unwrap(), u16::MAX);
+        // 0 is never handed out and 1 is still playing
+        assert_eq!(game.add_snake().unwrap(), first + 1);
+        assert_eq!(game.next_id, first + 2);
+    }
+
+    #[test]
+    fn removing_an_unknown_snake_changes_nothing() {
+        let mut game = game();
+        let id = game.add_snake().unwrap();
+        let minimum = game.food.minimum;
+        game.start_recording();
+        game.remove_snake(id + 1);
+        game.tick();
+        assert_eq!(game.food.minimum, minimum);
+        let replay = game.stop_recording().unwrap();
+        assert!(replay.ticks.iter().all(|tick| tick.events.is_empty()));
+    }
+
+    /// Lays a snake by hand, leaving food alone
+    fn place(game: &mut Game, id: u16, start: (u32, u32), direction: Direction, size: u16) {
+        let config = GameConfig {
+            start,
+            direction,
+            size,
+            ..game.config.clone()
+        };
+        let snake = Snake::new(&mut game.field, &config, id);
+        game.snakes.insert(id, snake);
+    }
+
+    fn head(game: &Game, id: u16) -> FieldPoint {
+        game.snakes[&id].nodes.back().unwrap().position
+    }
+
+    fn alive(game: &Game, id: u16) -> bool {
+        game.snakes[&id].is_alive()
+    }
+
+    #[test]
+    fn snake_dies_running_into_itself() {
+        let mut game = game();
+        place(&mut game, 1, (5, 5), Direction::Right, 5);
+        for to in [Direction::Down, Direction::Left, Direction::Up] {
+            assert_eq!(game.state, GameState::None);
+            game.head_to(1, to);
+            game.tick();
+        }
+        assert!(!alive(&game, 1));
+        assert_eq!(game.field.snake_count(), 0);
+        assert_eq!(game.state, GameState::Over);
+    }
+
+    #[test]
+    fn snake_dies_running_into_another() {
+        let mut game = game();
+        place(&mut game, 1, (2, 5), Direction::Right, 3);
+        place(&mut game, 2, (5, 3), Direction::Down, 5);
+        game.tick();
+        assert!(!alive(&game, 1));
+        assert!(alive(&game, 2));
+        assert_eq!(game.state, GameState::Over);
+    }
+
+    #[test]
+    fn heads_meeting_kill_both() {
+        let mut game = game();
+        place(&mut game, 1, (2, 5), Direction::Right, 3);
+        place(&mut game, 2, (8, 5), Direction::Left, 3);
+        place(&mut game, 3, (2, 20), Direction::Right, 3);
+        game.tick();
+        assert!(!alive(&game, 1));
+        assert!(!alive(&game, 2));
+        assert!(alive(&game, 3));
+        assert_eq!(game.state, GameState::Over);
+    }
+
+    #[test]
+    fn tail_moving_away_makes_room() {
+        let mut game = game();
+        place(&mut game, 1, (2, 5), Direction::Right, 3);
+        place(&mut game, 2, (5, 5), Direction::Down, 3);
+        place(&mut game, 3, (2, 20), Direction::Right, 3);
+        game.tick();
+        assert!(alive(&game, 1));
+        assert!(alive(&game, 2));
+        assert_eq!(head(&game, 1), FieldPoint { x: 5, y: 5 });
+        assert_eq!(game.state, GameState::None);
+    }
+
+    #[test]
+    fn growing_tail_stays_in_the_way() {
+        let mut game = game();
+        place(&mut game, 1, (2, 5), Direction::Right, 3);
+        place(&mut game, 2, (5, 5), Direction::Down, 3);
+        place(&mut game, 3, (2, 20), Direction::Right, 3);
+        let rules = game.config.food;
+        let food = Food::new(FoodType::Basic, FieldPoint { x: 5, y: 8 }, &rules);
+        game.food.set_food(food);
+        game.tick();
+        assert!(!alive(&game, 1));
+        assert_eq!(game.snakes[&2].nodes.len(), 4);
+    }
+
+    /// Snake 1 is longer than snake 2, both heads reach the same special food
+    fn contested(contest: FoodContest) -> Game {
+        let mut game = game();
+        game.config.food_contest = contest;
+        place(&mut game, 1, (1, 5), Direction::Right, 4);
+        place(&mut game, 2, (9, 5), Direction::Left, 3);
+        let rules = game.config.food;
+        let food = Food::new(FoodType::Whale, FieldPoint { x: 5, y: 5 }, &rules);
+        game.food.set_food(food);
+        game.tick();
+        game
+    }
+
+    #[test]
+    fn contested_food_goes_to_nobody() {
+        let game = contested(FoodContest::Nobody);
+        assert_eq!(game.snakes[&1].score, 0);
+        assert_eq!(game.snakes[&2].score, 0);
+        assert_eq!(game.snakes[&1].nodes.len(), 4);
+        // leftovers are spoiled
+        assert!(game.food.foods.is_empty());
+    }
+
+    #[test]
+    fn contested_food_goes_to_the_longer() {
+        let game = contested(FoodContest::Longer);
+        let weight = game.config.food.special_weight as u16;
+        assert_eq!(game.snakes[&1].score, weight);
+        assert_eq!(game.snakes[&2].score, 0);
+        assert_eq!(game.snakes[&1].nodes.len(), 5);
+        assert_eq!(game.snakes[&2].nodes.len(), 3);
+        assert!(game.food.foods.is_empty());
+    }
+
+    #[test]
+    fn tied_contest_goes_to_nobody() {
+        let mut game = game();
+        game.config.food_contest = FoodContest::Longer;
+        place(&mut game, 1, (2, 5), Direction::Right, 3);
+        place(&mut game, 2, (9, 5), Direction::Left, 3);
+        let rules = game.config.food;
+        let food = Food::new(FoodType::Whale, FieldPoint { x: 5, y: 5 }, &rules);
+        game.food.set_food(food);
+        game.tick();
+        assert_eq!(game.snakes[&1].score, 0);
+        assert_eq!(game.snakes[&2].score, 0);
+    }
+
+    #[test]
+    fn game_is_over_once_one_survives() {
+        let mut game = game();
+        place(&mut game, 1, (2, 5), Direction::Right, 3);
+        place(&mut game, 2, (2, 10), Direction::Right, 3);
+        place(&mut game, 3, (5, 13), Direction::Up, 5);
+        // 1 goes on, 2 runs into 3 and 3 is left with 1
+        game.tick();
+        assert!(alive(&game, 1));
+        assert!(!alive(&game, 2));
+        assert!(alive(&game, 3));
+        assert_eq!(game.state, GameState::None);
+        game.remove_snake(3);
+        game.tick();
+        assert_eq!(game.state, GameState::Over);
+        // nothing moves once it's over
+        let head = head(&game, 1);
+        game.tick();
+        assert_eq!(game.snakes[&1].nodes.back().unwrap().position, head);
     }
 }
@@ -0,0 +1,175 @@
+//! Area of interest. On fields larger than the screen each player only gets what's within a
+//! window around its head, plus a coarse minimap of where the other snakes are.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{
+    chunk::{chunk_of, ChunkId, ChunkView},
+    food::FoodField,
+    game::GameData,
+    rng::Rng,
+    types::{FieldPoint, GameConfig},
+};
+
+/// What a player gets each tick
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
+pub struct View {
+    pub data: GameData,
+    pub minimap: Minimap,
+}
+
+/// The field scaled down to a few tiles
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq, Default)]
+pub struct Minimap {
+    pub columns: u32,
+    pub rows: u32,
+    /// Heads of living snakes on each tile, row by row
+    pub snakes: Vec<u8>,
+}
+
+impl Minimap {
+    pub fn new(data: &GameData, columns: u32, rows: u32) -> Minimap {
+        let mut minimap = Minimap {
+            columns,
+            rows,
+            snakes: vec![0; (columns * rows) as usize],
+        };
+        let heads = data.snakes.values().filter(|snake| snake.is_alive());
+        for head in heads.filter_map(|snake| snake.nodes.back()) {
+            let tile = minimap.tile_of(&head.position, &data.config);
+            minimap.snakes[tile] = minimap.snakes[tile].saturating_add(1);
+        }
+        minimap
+    }
+
+    pub fn tile_of(&self, p: &FieldPoint, config: &GameConfig) -> usize {
+        let (width, height) = config.dim;
+        let x = p.x as u64 * self.columns as u64 / width as u64;
+        let y = p.y as u64 * self.rows as u64 / height as u64;
+        (y * self.columns as u64 + x) as usize
+    }
+}
+
+/// The game of a tick indexed by chunk, built once and then cut into each player's view
+#[derive(Debug)]
+pub struct Interest {
+    data: GameData,
+    /// Cells around the head each player gets, columns and rows
+    window: (u32, u32),
+    minimap: Minimap,
+    snakes: HashMap<ChunkId, Vec<u16>>,
+    food: HashMap<ChunkId, Vec<usize>>,
+    walls: HashMap<ChunkId, Vec<FieldPoint>>,
+}
+
+impl Interest {
+    pub fn new(data: GameData, window: (u32, u32), minimap: (u32, u32)) -> Interest {
+        let mut snakes: HashMap<ChunkId, Vec<u16>> = HashMap::new();
+        for (id, snake) in data.snakes.iter().filter(|(_id, s)| s.is_alive()) {
+            for node in snake.nodes.iter() {
+                let ids = snakes.entry(chunk_of(&node.position)).or_default();
+                if ids.last() != Some(id) {
+                    ids.push(*id);
+                }
+            }
+        }
+        let mut food: HashMap<ChunkId, Vec<usize>> = HashMap::new();
+        for (i, f) in data.food.foods.iter().enumerate() {
+            for cell in f.cells() {
+                let foods = food.entry(chunk_of(&cell)).or_default();
+                if foods.last() != Some(&i) {
+                    foods.push(i);
+                }
+            }
+        }
+        let mut walls: HashMap<ChunkId, Vec<FieldPoint>> = HashMap::new();
+        for wall in data.walls.iter() {
+            walls.entry(chunk_of(wall)).or_default().push(*wall);
+        }
+        let (columns, rows) = minimap;
+        Interest {
+            minimap: Minimap::new(&data, columns, rows),
+            data,
+            window,
+            snakes,
+            food,
+            walls,
+        }
+    }
+
+    pub fn data(&self) -> &GameData {
+        &self.data
+    }
+
+    /// Whatever is on the chunks the window centered on `center` touches. Snakes come whole
+    /// when any of their nodes is there, the `own` one always does. The map is left out, as its
+    /// walls and wormholes already are. So are the seed and the generator, which would tell
+    /// where food and snakes go next
+    pub fn view(&self, own: Option<u16>, center: &FieldPoint) -> View {
+        let data = &self.data;
+        let view = ChunkView::new(center, self.window, &data.config);
+        let chunks = view.chunks();
+
+        let near = chunks.iter().filter_map(|chunk| self.snakes.get(chunk));
+        let ids: BTreeSet<u16> = own.into_iter().chain(near.flatten().copied()).collect();
+        let snakes: BTreeMap<_, _> = ids
+            .iter()
+            .filter_map(|id| data.snakes.get(id).map(|snake| (*id, snake.clone())))
+            .collect();
+
+        let near = chunks.iter().filter_map(|chunk| self.food.get(chunk));
+        let foods: BTreeSet<usize> = near.flatten().copied().collect();
+        let mut food = FoodField::default();
+        for i in foods {
+            food.set_food(data.food.foods[i]);
+        }
+        food.minimum = data.food.minimum;
+        food.count = data.food.count;
+
+        let walls = chunks.iter().filter_map(|chunk| self.walls.get(chunk));
+        View {
+            data: GameData {
+                config: GameConfig {
+                    map: None,
+                    seed: 0,
+                    ..data.config.clone()
+                },
+                snakes,
+                food,
+                state: data.state.clone(),
+                rng: Rng::new(0),
+                walls: walls.flatten().copied().collect(),
+                wormholes: data
+                    .wormholes
+                    .iter()
+                    .copied()
+                    .filter(|(a, b)| view.contains(a) || view.contains(b))
+                    .collect(),
+                next_id: data.next_id,
+            },
+            minimap: self.minimap.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn views_keep_the_generator_out() {
+        let mut game = Game::new(GameConfig {
+            seed: 42,
+            ..GameConfig::default()
+        });
+        game.add_snake().unwrap();
+        game.tick();
+        let data = GameData::from_game(&game);
+        let interest = Interest::new(data, (64, 48), (8, 4));
+        let view = interest.view(Some(1), &FieldPoint { x: 0, y: 0 });
+        assert_eq!(view.data.config.seed, 0);
+        assert_eq!(view.data.rng, Rng::new(0));
+        assert_ne!(interest.data().rng, Rng::new(0));
+    }
+}
@@ -1,6 +1,18 @@
+pub mod chunk;
+pub mod delta;
+pub mod effect;
+pub mod error;
 pub mod food;
+pub mod free_cells;
 pub mod game;
+pub mod interest;
+pub mod map;
+pub mod protocol;
 pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod round;
 pub mod snake;
+pub mod spawn;
 pub mod types;
 pub mod utils;
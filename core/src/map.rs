@@ -0,0 +1,217 @@
+//! Plain text arenas. A map starts with optional `key: value` header lines followed by the grid:
+//!
+//! ```text
+//! name: Arena
+//! size: 30x20
+//! tick: 250
+//! wrap: both
+//!
+//! ##############################
+//! #>...........................#
+//! #......W.............W.......#
+//! ```
+//!
+//! Grid cells are `#` for walls, `.` or a space for empty cells, `W` for wormholes, paired in
+//! reading order, and spawn points facing `>`, `<`, `^`, `v` (`S` is the same as `>`).
+//! A snake hatches with its tail on the spawn point. Without `size` the dimensions come
+//! from the grid itself, shorter rows are padded with empty cells.
+//! `wrap` is one of `both`, `none`, `horizontal` or `vertical`.
+
+use std::fmt;
+
+use crate::types::{Direction, FieldPoint, Wrap};
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq)]
+pub struct Spawn {
+    pub position: FieldPoint,
+    pub direction: Direction,
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
+pub struct Map {
+    pub name: String,
+    pub dim: (u32, u32),
+    /// Milliseconds between ticks
+    pub tick: Option<u16>,
+    pub wrap: Wrap,
+    pub walls: Vec<FieldPoint>,
+    pub spawns: Vec<Spawn>,
+    pub wormholes: Vec<(FieldPoint, FieldPoint)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for MapError {}
+
+fn error(line: usize, column: usize, message: impl Into<String>) -> MapError {
+    MapError {
+        line: line + 1,
+        column: column + 1,
+        message: message.into(),
+    }
+}
+
+/// Splits a `key: value` line, grid rows never have a colon
+fn header(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let width = width.trim().parse().ok()?;
+    let height = height.trim().parse().ok()?;
+    Some((width, height))
+}
+
+impl Map {
+    pub fn parse(text: &str) -> Result<Map, MapError> {
+        let mut map = Map {
+            name: String::new(),
+            dim: (0, 0),
+            tick: None,
+            wrap: Wrap::Both,
+            walls: Vec::new(),
+            spawns: Vec::new(),
+            wormholes: Vec::new(),
+        };
+        let mut size = None;
+
+        let mut lines = text.lines().enumerate().peekable();
+        while let Some((n, line)) = lines.peek() {
+            let Some((key, value)) = header(line) else {
+                break;
+            };
+            let column = line.find(':').unwrap();
+            match key {
+                "name" => map.name = value.to_owned(),
+                "size" => {
+                    let dim = parse_size(value).filter(|(w, h)| *w > 0 && *h > 0);
+                    size = Some(dim.ok_or_else(|| error(*n, column, "size must be WIDTHxHEIGHT"))?);
+                }
+                "tick" => {
+                    let tick = value.parse().ok().filter(|tick| *tick > 0);
+                    map.tick =
+                        Some(tick.ok_or_else(|| error(*n, column, "tick must be milliseconds"))?);
+                }
+                "wrap" => {
+                    map.wrap = value.parse().map_err(|_| {
+                        error(
+                            *n,
+                            column,
+                            "wrap must be both, none, horizontal or vertical",
+                        )
+                    })?;
+                }
+                _ => return Err(error(*n, 0, format!("unknown header '{key}'"))),
+            }
+            lines.next();
+        }
+
+        let mut rows: Vec<(usize, &str)> = lines
+            .skip_while(|(_n, line)| line.trim().is_empty())
+            .collect();
+        while rows.last().is_some_and(|(_n, line)| line.trim().is_empty()) {
+            rows.pop();
+        }
+        let last_line = text.lines().count();
+        if rows.is_empty() {
+            return Err(error(last_line, 0, "map has no grid"));
+        }
+
+        let (width, height) = size.unwrap_or_else(|| {
+            let width = rows.iter().map(|(_n, row)| row.chars().count()).max();
+            (width.unwrap_or(0) as u32, rows.len() as u32)
+        });
+        if rows.len() > height as usize {
+            let (n, _row) = rows[height as usize];
+            return Err(error(n, 0, format!("grid is taller than {height} rows")));
+        }
+        map.dim = (width, height);
+
+        let mut wormholes: Vec<(usize, usize, FieldPoint)> = Vec::new();
+        for (y, (n, row)) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if x >= width as usize {
+                    if c.is_whitespace() {
+                        continue;
+                    }
+                    return Err(error(*n, x, format!("grid is wider than {width} columns")));
+                }
+                let position = FieldPoint {
+                    x: x as u32,
+                    y: y as u32,
+                };
+                let direction = match c {
+                    '#' => {
+                        map.walls.push(position);
+                        continue;
+                    }
+                    'W' => {
+                        wormholes.push((*n, x, position));
+                        continue;
+                    }
+                    '.' | ' ' => continue,
+                    'S' | '>' => Direction::Right,
+                    '<' => Direction::Left,
+                    '^' => Direction::Up,
+                    'v' => Direction::Down,
+                    c => return Err(error(*n, x, format!("unexpected character '{c}'"))),
+                };
+                map.spawns.push(Spawn {
+                    position,
+                    direction,
+                });
+            }
+        }
+
+        if wormholes.len() % 2 == 1 {
+            let (n, x, _p) = wormholes[wormholes.len() - 1];
+            return Err(error(n, x, "wormhole without a pair"));
+        }
+        map.wormholes = wormholes
+            .chunks(2)
+            .map(|pair| (pair[0].2, pair[1].2))
+            .collect();
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_errors_point_at_the_colon() {
+        let err = Map::parse("tick: abc\n#\n").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 5: tick must be milliseconds"
+        );
+        let err = Map::parse("name: x\nsize:3\n#\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 5));
+        let err = Map::parse("  wrap: around\n#\n").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 7));
+    }
+}
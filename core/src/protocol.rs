@@ -0,0 +1,94 @@
+//! Messages between the server and the browser, each websocket frame carries one of them
+//! bincoded. A connection goes:
+//!
+//! 1. the client sends `Hello` with the version it speaks, or `Resume` with the session token
+//!    of a socket that dropped
+//! 2. the server answers `Welcome` with the id of the snake it hatched or took back and the
+//!    session token, none for spectators, or `Unsupported` with its own version and closes the
+//!    socket
+//! 3. the server sends a `Frame` every tick followed by a `Ping`, the client answers pings with
+//!    `Pong`, turns with `Turn` and asks for a `Keyframe` when a frame went missing
+//! 4. the server sends the `Round` phase whenever it moves on, in the lobby the client sends
+//!    `Ready` to get the round going
+//! 5. spectators cycle through the snakes with `FollowNext` and `FollowPrevious`, the server
+//!    tells which one with `Following`. They ask to `Join`, and once the server hatches their
+//!    snake in the lobby it sends `Player` with its id and session token
+//!
+//! Any change to these enums, or to what a frame carries, bumps `PROTOCOL_VERSION`.
+
+use crate::{
+    delta::Frame,
+    error::Error,
+    round::Phase,
+    types::Direction,
+    utils::{decode, encode},
+};
+
+pub const PROTOCOL_VERSION: u16 = 5;
+
+/// Lets a new socket take over the snake of one that dropped
+pub type SessionToken = u128;
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    Hello {
+        version: u16,
+    },
+    Turn(Direction),
+    /// Echoes the time of a `Ping`
+    Pong(u64),
+    Keyframe,
+    Ready,
+    Join,
+    FollowNext,
+    FollowPrevious,
+    Resume {
+        version: u16,
+        token: SessionToken,
+    },
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone)]
+pub enum ServerMessage {
+    Welcome {
+        version: u16,
+        snake_id: Option<u16>,
+        token: Option<SessionToken>,
+    },
+    Unsupported {
+        version: u16,
+    },
+    Frame(Frame),
+    /// Microseconds since the epoch when it was sent
+    Ping(u64),
+    Round(Phase),
+    /// The spectator plays with this snake from now on
+    Player {
+        snake_id: u16,
+        token: SessionToken,
+    },
+    /// Snake the spectator is following
+    Following(u16),
+}
+
+impl ClientMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ClientMessage, Error> {
+        let (message, _size) = decode(bytes)?;
+        Ok(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode(self).unwrap()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ServerMessage, Error> {
+        let (message, _size) = decode(bytes)?;
+        Ok(message)
+    }
+}
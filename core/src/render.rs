@@ -1,7 +1,13 @@
-use crate::{food::FoodField, snake::Snake, types::Food};
+use crate::{
+    food::FoodField,
+    snake::Snake,
+    types::{FieldPoint, Food},
+};
 
 pub trait GameRender: Send + Sync {
     fn snake(&mut self, snake: &Snake, food_field: &FoodField);
     fn food(&mut self, food: &Food);
     fn score(&mut self, score: u16);
+    fn wall(&mut self, p: &FieldPoint);
+    fn portal(&mut self, p: &FieldPoint);
 }
@@ -0,0 +1,348 @@
+use std::{fmt, fs, io, path::Path};
+
+use bincode::error::{DecodeError, EncodeError};
+
+use crate::{
+    error::Error,
+    game::{Game, GameData},
+    types::Direction,
+    utils::{decode, encode},
+};
+
+pub const REPLAY_MAGIC: [u8; 4] = *b"SNKR";
+pub const REPLAY_VERSION: u16 = 6;
+
+/// Anything that changes a `Game` besides its own ticking
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq)]
+pub enum ReplayEvent {
+    Join(u16),
+    Leave(u16),
+    Turn(u16, Direction),
+}
+
+/// Events applied right before a tick, in the same order they happened
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Default)]
+pub struct ReplayTick {
+    pub events: Vec<ReplayEvent>,
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug)]
+pub struct ReplayData {
+    /// Snapshot taken when recording started. It carries config, seed and rng state
+    pub start: GameData,
+    pub ticks: Vec<ReplayTick>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u16),
+    Encode(EncodeError),
+    Decode(DecodeError),
+    /// Playback no longer follows the recording, a snake joined under another id or not at all
+    Diverged {
+        tick: usize,
+        expected: u16,
+        got: Option<u16>,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "{err}"),
+            ReplayError::BadMagic => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "version {version} isn't supported, expected {REPLAY_VERSION}"
+                )
+            }
+            ReplayError::Encode(err) => write!(f, "can't encode: {err}"),
+            ReplayError::Decode(err) => write!(f, "can't decode: {err}"),
+            ReplayError::Diverged {
+                tick,
+                expected,
+                got: Some(got),
+            } => write!(f, "tick {tick}: snake {expected} joined as {got}"),
+            ReplayError::Diverged {
+                tick,
+                expected,
+                got: None,
+            } => write!(f, "tick {tick}: snake {expected} found no room to join"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl ReplayData {
+    /// Header is the magic bytes followed by the format version, then the bincoded data
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ReplayError> {
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.extend(encode(REPLAY_VERSION).map_err(ReplayError::Encode)?);
+        bytes.extend(encode(self).map_err(ReplayError::Encode)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ReplayData, ReplayError> {
+        let body = bytes
+            .strip_prefix(&REPLAY_MAGIC)
+            .ok_or(ReplayError::BadMagic)?;
+        let (version, size): (u16, usize) = decode(body).map_err(ReplayError::Decode)?;
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let (data, _size): (ReplayData, usize) =
+            decode(&body[size..]).map_err(ReplayError::Decode)?;
+        Ok(data)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<ReplayData, ReplayError> {
+        ReplayData::from_bytes(&fs::read(path)?)
+    }
+}
+
+/// Collects a game's events, see `Game::start_recording`
+#[derive(Debug)]
+pub struct Recorder {
+    data: ReplayData,
+    pending: ReplayTick,
+}
+
+impl Recorder {
+    pub fn new(game: &Game) -> Recorder {
+        Recorder {
+            data: ReplayData {
+                start: GameData::from_game(game),
+                ticks: Vec::new(),
+            },
+            pending: ReplayTick::default(),
+        }
+    }
+
+    pub fn push(&mut self, event: ReplayEvent) {
+        self.pending.events.push(event);
+    }
+
+    pub fn tick(&mut self) {
+        let tick = std::mem::take(&mut self.pending);
+        self.data.ticks.push(tick);
+    }
+
+    /// Events that didn't reach a tick yet are left out, they had no visible effect
+    pub fn finish(self) -> ReplayData {
+        self.data
+    }
+}
+
+/// Rebuilds a recorded game tick by tick
+#[derive(Debug)]
+pub struct Replay {
+    data: ReplayData,
+    start: Vec<u8>,
+    position: usize,
+    pub game: Game,
+}
+
+impl Replay {
+    pub fn new(data: ReplayData) -> Result<Replay, Error> {
+        let start = encode(&data.start).map_err(ReplayError::Encode)?;
+        data.start.config.validate()?;
+        let mut game = Game::new(data.start.config.clone());
+        game.set_game_data(start.clone())?;
+        Ok(Replay {
+            data,
+            start,
+            position: 0,
+            game,
+        })
+    }
+
+    /// Ticks played so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.ticks.is_empty()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.len()
+    }
+
+    /// Plays the next tick, returns false when there's nothing left. Once it fails the game is
+    /// left halfway through the tick and can only be seeked back
+    pub fn step(&mut self) -> Result<bool, ReplayError> {
+        let Some(tick) = self.data.ticks.get(self.position) else {
+            return Ok(false);
+        };
+        for event in tick.events.iter() {
+            match *event {
+                // only joins that found room were recorded, so they find it again
+                ReplayEvent::Join(id) => {
+                    let got = self.game.add_snake().ok();
+                    if got != Some(id) {
+                        return Err(ReplayError::Diverged {
+                            tick: self.position,
+                            expected: id,
+                            got,
+                        });
+                    }
+                }
+                ReplayEvent::Leave(id) => self.game.remove_snake(id),
+                ReplayEvent::Turn(id, to) => self.game.head_to(id, to),
+            }
+        }
+        self.game.tick();
+        self.position += 1;
+        Ok(true)
+    }
+
+    /// Going backwards replays from the start, as ticks can't be undone
+    pub fn seek(&mut self, position: usize) -> Result<(), ReplayError> {
+        let position = position.min(self.len());
+        if position < self.position {
+            self.game = Game::new(self.data.start.config.clone());
+            let start = self.game.set_game_data(self.start.clone());
+            start.expect("replay start was checked on new");
+            self.position = 0;
+        }
+        while self.position < position {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Direction, GameConfig};
+
+    fn recording(events: Vec<ReplayEvent>) -> ReplayData {
+        let mut game = Game::new(GameConfig::default());
+        game.start_recording();
+        for _ in 0..3 {
+            game.tick();
+        }
+        let mut data = game.stop_recording().unwrap();
+        data.ticks[1].events = events;
+        data
+    }
+
+    #[test]
+    fn plays_recorded_joins() {
+        let mut replay = Replay::new(recording(vec![ReplayEvent::Join(1)])).unwrap();
+        while replay.step().unwrap() {}
+        assert!(replay.is_finished());
+        assert!(replay.game.snakes.contains_key(&1));
+    }
+
+    #[test]
+    fn fails_when_a_join_gets_another_id() {
+        let data = recording(vec![ReplayEvent::Join(1), ReplayEvent::Join(3)]);
+        let mut replay = Replay::new(data).unwrap();
+        assert!(replay.step().unwrap());
+        assert!(matches!(
+            replay.step(),
+            Err(ReplayError::Diverged {
+                tick: 1,
+                expected: 3,
+                got: Some(2)
+            })
+        ));
+        let err = replay.seek(3).unwrap_err();
+        assert_eq!(err.to_string(), "tick 1: snake 1 joined as 3");
+        assert!(replay.seek(0).is_ok());
+    }
+
+    /// Plays `data` through and hands the game back
+    fn played(data: ReplayData) -> Game {
+        let mut replay = Replay::new(data).unwrap();
+        while replay.step().unwrap() {}
+        replay.game
+    }
+
+    /// Leaving shuffles nothing the data doesn't carry, so food lands where it did live
+    #[test]
+    fn plays_games_some_snakes_left_before_recording() {
+        for seed in 0..20 {
+            let mut game = Game::new(GameConfig {
+                seed,
+                ..GameConfig::default()
+            });
+            let ids: Vec<u16> = (0..6).map(|_| game.add_snake().unwrap()).collect();
+            for id in &ids[..4] {
+                game.remove_snake(*id);
+            }
+            game.start_recording();
+            for _ in 0..50 {
+                game.tick();
+            }
+            let data = game.stop_recording().unwrap();
+            let replayed = played(data);
+            assert_eq!(
+                replayed.encode_game_data(),
+                game.encode_game_data(),
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn plays_a_recorded_game_back() {
+        const TURNS: [Direction; 4] = [
+            Direction::Up,
+            Direction::Left,
+            Direction::Down,
+            Direction::Right,
+        ];
+        let mut game = Game::new(GameConfig {
+            seed: 9,
+            ..GameConfig::default()
+        });
+        game.add_snake().unwrap();
+        game.add_snake().unwrap();
+        game.start_recording();
+        for tick in 0..120 {
+            match tick {
+                10 | 40 => {
+                    game.add_snake().unwrap();
+                }
+                60 => game.remove_snake(1),
+                _ => {}
+            }
+            let ids: Vec<u16> = game.snakes.keys().copied().collect();
+            for id in ids {
+                if (tick + id as usize) % 7 == 0 {
+                    game.head_to(id, TURNS[(tick / 7 + id as usize) % 4]);
+                }
+            }
+            game.tick();
+        }
+        let data = game.stop_recording().unwrap();
+        let events: Vec<ReplayEvent> = data.ticks.iter().flat_map(|t| t.events.clone()).collect();
+        assert!(events.contains(&ReplayEvent::Join(4)));
+        assert!(events.contains(&ReplayEvent::Leave(1)));
+        assert!(events.iter().any(|e| matches!(e, ReplayEvent::Turn(..))));
+        assert_eq!(played(data).encode_game_data(), game.encode_game_data());
+    }
+}
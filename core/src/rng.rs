@@ -0,0 +1,48 @@
+use std::ops::Range;
+
+/// Small xorshift64* generator. Its whole state is a single word that travels along with
+/// `GameData`, so the same seed and inputs always lead to the same food placement.
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // splitmix64 scramble, also keeps a zero seed away from xorshift's dead state
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        let state = if z == 0 { 0x9e37_79b9_7f4a_7c15 } else { z };
+        Rng { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Panics on an empty range, same as `rand::Rng::gen_range`
+    pub fn gen_range(&mut self, range: Range<u16>) -> u16 {
+        assert!(!range.is_empty(), "cannot sample empty range");
+        let span = (range.end - range.start) as u64;
+        range.start + (self.next_u64() % span) as u16
+    }
+}
+
+/// Seed for games that don't need to be reproduced
+pub fn random_seed() -> u64 {
+    let mut bytes = [0u8; 8];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        return now.as_nanos() as u64;
+    }
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,212 @@
+//! Rounds as the server runs them. Players gather in the lobby and say when they're ready, once
+//! all of them are a countdown starts, then the round is played until the game is over and its
+//! standings are shown for a while before going back to the lobby with a new game.
+
+use std::{collections::BTreeSet, time::Duration};
+
+use crate::{game::Game, types::GameState};
+
+/// Time between everyone being ready and the round starting
+pub const COUNTDOWN: Duration = Duration::from_secs(3);
+/// Time the standings are shown
+pub const STANDINGS: Duration = Duration::from_secs(5);
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
+pub enum Phase {
+    Lobby {
+        /// Players who are ready, out of `players`
+        ready: Vec<u16>,
+        players: u16,
+    },
+    /// Seconds left before the round starts
+    Countdown(u8),
+    Playing,
+    /// Best first
+    RoundOver(Vec<Standing>),
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub id: u16,
+    pub score: u16,
+    /// Survivors place ahead of the rest
+    pub alive: bool,
+}
+
+/// What the game under the round has to go through
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    /// Countdown is over, the game starts playing
+    Start,
+    /// The game is over and its standings are out
+    Over,
+    /// Back in the lobby, a new game takes the place of the old one
+    Restart,
+}
+
+#[derive(Debug)]
+pub struct Round {
+    phase: Phase,
+    ready: BTreeSet<u16>,
+    /// Time between ticks
+    tick: Duration,
+    /// Ticks left on the countdown or the standings
+    ticks_left: u32,
+}
+
+impl Round {
+    pub fn new(tick: Duration) -> Round {
+        Round {
+            phase: Phase::Lobby {
+                ready: Vec::new(),
+                players: 0,
+            },
+            ready: BTreeSet::new(),
+            tick,
+            ticks_left: 0,
+        }
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// Only counts in the lobby
+    pub fn ready(&mut self, id: u16) {
+        if let Phase::Lobby { .. } = self.phase {
+            self.ready.insert(id);
+        }
+    }
+
+    /// Goes on by a tick, telling what `game` has to go through when the phase moves on
+    pub fn tick(&mut self, game: &Game) -> Option<Step> {
+        match self.phase {
+            Phase::Lobby { .. } => {
+                self.ready.retain(|id| game.snakes.contains_key(id));
+                let players = game.snakes.len();
+                if players > 0 && self.ready.len() == players {
+                    self.ticks_left = self.ticks(COUNTDOWN);
+                    self.phase = Phase::Countdown(self.seconds_left());
+                } else {
+                    self.phase = Phase::Lobby {
+                        ready: self.ready.iter().copied().collect(),
+                        players: players as u16,
+                    };
+                }
+                None
+            }
+            Phase::Countdown(_) => {
+                if game.snakes.is_empty() {
+                    self.lobby();
+                    return None;
+                }
+                self.ticks_left = self.ticks_left.saturating_sub(1);
+                if self.ticks_left > 0 {
+                    self.phase = Phase::Countdown(self.seconds_left());
+                    return None;
+                }
+                self.phase = Phase::Playing;
+                Some(Step::Start)
+            }
+            Phase::Playing => {
+                // everyone left, nobody is there to finish the round
+                if game.snakes.is_empty() {
+                    self.lobby();
+                    return Some(Step::Restart);
+                }
+                if game.state != GameState::Over {
+                    return None;
+                }
+                self.ticks_left = self.ticks(STANDINGS);
+                self.phase = Phase::RoundOver(standings(game));
+                Some(Step::Over)
+            }
+            Phase::RoundOver(_) => {
+                self.ticks_left = self.ticks_left.saturating_sub(1);
+                if self.ticks_left > 0 {
+                    return None;
+                }
+                self.lobby();
+                Some(Step::Restart)
+            }
+        }
+    }
+
+    fn lobby(&mut self) {
+        self.ready.clear();
+        self.phase = Phase::Lobby {
+            ready: Vec::new(),
+            players: 0,
+        };
+    }
+
+    /// Ticks it takes for `duration` to go by, at least one
+    fn ticks(&self, duration: Duration) -> u32 {
+        let tick = self.tick.as_micros().max(1);
+        duration.as_micros().div_ceil(tick).max(1) as u32
+    }
+
+    fn seconds_left(&self) -> u8 {
+        let left = self.tick * self.ticks_left;
+        left.as_millis().div_ceil(1000) as u8
+    }
+}
+
+/// Survivors first, then by score
+pub fn standings(game: &Game) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = game
+        .snakes
+        .values()
+        .map(|snake| Standing {
+            id: snake.id,
+            score: snake.score,
+            alive: snake.is_alive(),
+        })
+        .collect();
+    standings.sort_by(|a, b| b.alive.cmp(&a.alive).then(b.score.cmp(&a.score)));
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::GameConfig;
+
+    const TICK: Duration = Duration::from_millis(500);
+
+    /// Ready players go through the countdown into a round
+    fn playing(game: &mut Game) -> Round {
+        let mut round = Round::new(TICK);
+        let id = game.add_snake().unwrap();
+        round.tick(game);
+        round.ready(id);
+        while round.tick(game) != Some(Step::Start) {}
+        round
+    }
+
+    #[test]
+    fn round_ends_when_everyone_leaves() {
+        let mut game = Game::new(GameConfig::default());
+        let mut round = playing(&mut game);
+        assert_eq!(*round.phase(), Phase::Playing);
+        let ids: Vec<u16> = game.snakes.keys().copied().collect();
+        for id in ids {
+            game.remove_snake(id);
+        }
+        assert_eq!(round.tick(&game), Some(Step::Restart));
+        assert!(matches!(round.phase(), Phase::Lobby { .. }));
+    }
+
+    #[test]
+    fn countdown_goes_back_to_the_lobby_when_everyone_leaves() {
+        let mut game = Game::new(GameConfig::default());
+        let mut round = Round::new(TICK);
+        let id = game.add_snake().unwrap();
+        round.ready(id);
+        round.tick(&game);
+        assert!(matches!(round.phase(), Phase::Countdown(_)));
+        game.remove_snake(id);
+        assert_eq!(round.tick(&game), None);
+        assert!(matches!(round.phase(), Phase::Lobby { .. }));
+    }
+}
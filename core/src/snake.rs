@@ -1,19 +1,27 @@
-use crate::types::*;
+use crate::{effect::*, types::*};
 use std::collections::VecDeque;
 
-#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy)]
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq)]
 pub struct SnakeNode {
     pub direction: Direction,
     pub position: FieldPoint,
     pub stuffed: bool,
 }
 
-#[derive(bincode::Encode, bincode::Decode, Debug, Clone)]
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnakeState {
+    Alive,
+    Dead,
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, PartialEq)]
 pub struct Snake {
     pub nodes: VecDeque<SnakeNode>,
     pub direction: WrappableDirection,
     pub score: u16,
     pub id: u16,
+    pub state: SnakeState,
+    pub effects: Vec<Effect>,
 }
 
 impl Snake {
@@ -29,22 +37,72 @@ impl Snake {
         let mut snake = Snake {
             id,
             nodes: VecDeque::new(),
-            direction: WrappableDirection { to, max },
+            direction: WrappableDirection {
+                to,
+                max,
+                wrap: config.wrap,
+            },
             score: 0,
+            state: SnakeState::Alive,
+            effects: Vec::new(),
         };
 
         snake.egg_hatch(field, start, config.size);
         snake
     }
 
+    pub fn is_alive(&self) -> bool {
+        self.state == SnakeState::Alive
+    }
+
+    /// Marks the snake as dead and releases its body from the field
+    pub fn die(&mut self, field: &mut Field) {
+        self.state = SnakeState::Dead;
+        self.effects.clear();
+        for node in self.nodes.iter() {
+            field.set(&node.position, false);
+        }
+    }
+
     pub fn head_to(&mut self, to: Direction) -> bool {
-        if self.should_ignore_turn(to) {
+        let to = match self.has_effect(EffectKind::Reversed) {
+            true => opposite_of(to),
+            false => to,
+        };
+        if !self.is_alive() || self.should_ignore_turn(to) {
             return false;
         }
         self.direction.to = to;
         true
     }
 
+    pub fn effect(&self, kind: EffectKind) -> Option<&Effect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn has_effect(&self, kind: EffectKind) -> bool {
+        self.effect(kind).is_some()
+    }
+
+    /// True on the ticks an every other tick effect acts
+    pub fn effect_on_beat(&self, kind: EffectKind) -> bool {
+        self.effect(kind).is_some_and(|effect| effect.on_beat())
+    }
+
+    /// Eating the same kind again restarts its duration
+    pub fn add_effect(&mut self, kind: EffectKind) {
+        self.effects.retain(|effect| effect.kind != kind);
+        self.effects.push(Effect::new(kind));
+    }
+
+    pub fn tick_effects(&mut self) {
+        self.effects.retain_mut(|effect| {
+            effect.ticks_left = effect.ticks_left.saturating_sub(1);
+            effect.ticks_left > 0
+        });
+    }
+
+    /// The cell right ahead of the head
     pub fn next_head(&self) -> SnakeNode {
         let head = self.nodes.back().unwrap();
         let position = head.position.wrapping_add(self.direction);
@@ -55,19 +113,34 @@ impl Snake {
         }
     }
 
+    /// Where the head actually goes, wormholes included. `None` when it hits a solid edge
+    pub fn next_head_through(&self, wormholes: &[Wormhole]) -> Option<SnakeNode> {
+        let head = self.nodes.back().unwrap();
+        let position = head.position.warping_add(self.direction, wormholes)?;
+        Some(SnakeNode {
+            direction: self.direction.to,
+            position,
+            stuffed: false,
+        })
+    }
+
     pub fn should_ignore_turn(&self, to: Direction) -> bool {
         let direction = self.nodes.back().unwrap().direction;
         opposite_of(direction) == to || direction == to
     }
 
+    /// Lays the snake from its tail at `location`, cells are expected to be free
     pub fn egg_hatch(&mut self, field: &mut Field, location: FieldPoint, size: u16) {
         self.nodes.push_front(SnakeNode {
             direction: self.direction.to,
             position: location,
             stuffed: false,
         });
+        field.set(&location, true);
         for _ in 1..size {
-            let next_head = self.next_head();
+            let Some(next_head) = self.next_head_through(&field.wormholes) else {
+                break;
+            };
             let SnakeNode { position, .. } = next_head;
             self.nodes.push_back(next_head);
             field.set(&position, true);
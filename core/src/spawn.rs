@@ -0,0 +1,156 @@
+use crate::{
+    error::Error,
+    food::FoodField,
+    map::Spawn,
+    rng::Rng,
+    types::{Direction, Field, FieldPoint, Wrap, WrappableDirection},
+};
+
+/// Empty cells kept ahead of a new snake, so it doesn't hatch facing something
+pub const RUNWAY: u16 = 3;
+/// Cells kept between a new snake and the heads of the others, when there's room for it
+pub const HEAD_CLEARANCE: u32 = 2;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Right,
+    Direction::Down,
+    Direction::Left,
+    Direction::Up,
+];
+
+/// Finds a straight free run where a snake can hatch, its tail on the spawn position
+pub struct SpawnPlanner<'a> {
+    pub field: &'a Field,
+    pub food: &'a FoodField,
+    pub size: u16,
+    pub wrap: Wrap,
+    pub heads: Vec<FieldPoint>,
+}
+
+impl SpawnPlanner<'_> {
+    /// Tries the `preferred` spawns in order, then searches the free cells starting from a
+    /// random one. Keeping away from other heads is dropped only when nothing else fits.
+    pub fn plan(&self, preferred: &[Spawn], rng: &mut Rng) -> Result<Spawn, Error> {
+        let turn = rng.gen_range(0..DIRECTIONS.len() as u16) as usize;
+
+        for clearance in [true, false] {
+            if let Some(spawn) = preferred.iter().find(|s| self.fits(s, clearance)) {
+                return Ok(*spawn);
+            }
+            for position in self.field.free_cells(rng) {
+                for d in 0..DIRECTIONS.len() {
+                    let direction = DIRECTIONS[(turn + d) % DIRECTIONS.len()];
+                    let spawn = Spawn {
+                        position,
+                        direction,
+                    };
+                    if self.fits(&spawn, clearance) {
+                        return Ok(spawn);
+                    }
+                }
+            }
+        }
+        Err(Error::FieldFull)
+    }
+
+    /// Body cells must be free of anything, runway cells only of obstacles. A run that leaves
+    /// the field or comes back on itself doesn't fit
+    fn fits(&self, spawn: &Spawn, clearance: bool) -> bool {
+        let max = FieldPoint {
+            x: self.field.width,
+            y: self.field.height,
+        };
+        let direction = WrappableDirection {
+            to: spawn.direction,
+            max,
+            wrap: self.wrap,
+        };
+        let total = self.size.saturating_add(RUNWAY);
+        let mut run: Vec<FieldPoint> = Vec::with_capacity(total as usize);
+        let mut p = spawn.position;
+        for i in 0..total {
+            if p.x >= max.x || p.y >= max.y || run.contains(&p) || self.field.filled(&p) {
+                return false;
+            }
+            if i < self.size && self.food.has_at(&p).is_some() {
+                return false;
+            }
+            if clearance && self.near_head(&p) {
+                return false;
+            }
+            run.push(p);
+            if i + 1 < total {
+                match p.bounded_add(direction) {
+                    Some(next) => p = next,
+                    None => return false,
+                }
+            }
+        }
+        true
+    }
+
+    fn near_head(&self, p: &FieldPoint) -> bool {
+        self.heads
+            .iter()
+            .any(|head| head.x.abs_diff(p.x) + head.y.abs_diff(p.y) <= HEAD_CLEARANCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        game::Game,
+        types::{FieldPoint, GameConfig},
+    };
+
+    fn hatch_all(config: GameConfig) -> Game {
+        let mut game = Game::new(config);
+        while game.add_snake().is_ok() {}
+        game
+    }
+
+    fn assert_no_overlap(game: &Game) {
+        let nodes: Vec<FieldPoint> = game
+            .snakes
+            .values()
+            .flat_map(|snake| snake.nodes.iter().map(|node| node.position))
+            .collect();
+        let cells: HashSet<&FieldPoint> = nodes.iter().collect();
+        assert_eq!(cells.len(), nodes.len(), "{nodes:?}");
+        assert_eq!(game.field.snake_count(), nodes.len() as u64);
+    }
+
+    #[test]
+    fn never_hatches_on_itself() {
+        for (dim, size) in [((15, 1), 3), ((1, 15), 3), ((4, 4), 6), ((3, 3), 2)] {
+            let config = GameConfig {
+                dim,
+                size,
+                start: (0, 0),
+                ..GameConfig::default()
+            };
+            assert_no_overlap(&hatch_all(config));
+        }
+    }
+
+    #[test]
+    fn start_out_of_the_field_is_not_free() {
+        let config = GameConfig {
+            dim: (10, 10),
+            size: 2,
+            ..GameConfig::default()
+        };
+        let mut game = Game::new(config);
+        // as when a small map comes without spawns
+        game.config.start = (40, 30);
+        let id = game.add_snake().unwrap();
+        let snake = &game.snakes[&id];
+        assert!(snake
+            .nodes
+            .iter()
+            .all(|node| game.config.contains(&node.position)));
+        assert_no_overlap(&game);
+    }
+}
@@ -1,5 +1,15 @@
+use std::{collections::BTreeMap, str::FromStr};
+
 use fixedbitset::FixedBitSet;
 
+use crate::{
+    chunk::{chunk_of, local_idx, Chunk, ChunkId, CHUNK},
+    error::{invalid, Error},
+    food::FoodRules,
+    map::Map,
+    rng::Rng,
+};
+
 #[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FoodType {
     Basic,
@@ -21,30 +31,37 @@ pub struct Food {
 }
 
 impl Food {
-    pub fn new(shape: FoodType, p: FieldPoint) -> Food {
+    pub fn new(shape: FoodType, p: FieldPoint, rules: &FoodRules) -> Food {
         match shape {
             FoodType::Basic => Food {
                 ticks_left: 0,
                 size: 1,
-                weight: 8,
+                weight: rules.basic_weight,
                 shape,
                 location: p,
             },
             _ => Food {
-                ticks_left: 30,
-                size: 2,
-                weight: 45,
+                ticks_left: rules.special_ticks,
+                size: rules.special_size,
+                weight: rules.special_weight,
                 shape,
                 location: p,
             },
         }
     }
-    // not generic but does the job
+    /// Footprint of the food, `size` cells to the right starting from its location. Cut short
+    /// where the coordinates would overflow, such food doesn't pass `GameData::validate`
+    pub fn cells(&self) -> impl Iterator<Item = FieldPoint> + '_ {
+        let FieldPoint { x, y } = self.location;
+        (0..self.size as u32).map_while(move |dx| {
+            Some(FieldPoint {
+                x: x.checked_add(dx)?,
+                y,
+            })
+        })
+    }
     pub fn is_at(&self, p: &FieldPoint) -> bool {
-        match self.shape {
-            FoodType::Basic => self.location.eq(p),
-            _ => self.location.eq(p) || (self.location.add_tuple((1, 0))).eq(p),
-        }
+        self.cells().any(|cell| cell.eq(p))
     }
 }
 
@@ -56,50 +73,205 @@ pub enum GameState {
     Over,
 }
 
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Empty,
+    Snake,
+    Wall,
+    Portal,
+}
+
+/// Both ends of a wormhole, a snake entering one comes out from the other
+pub type Wormhole = (FieldPoint, FieldPoint);
+
+/// Occupancy of the field, split in chunks that are only allocated once something lands on them.
+/// Walls and wormhole portals are static and kept apart from the snake bodies. Changes go through
+/// the setters so the free cells of each chunk stay in sync
 #[derive(Debug)]
 pub struct Field {
-    pub width: u16,
-    pub height: u16,
-    pub bit_set: FixedBitSet,
+    pub width: u32,
+    pub height: u32,
+    pub wormholes: Vec<Wormhole>,
+    chunks: BTreeMap<ChunkId, Chunk>,
+    filled: u64,
 }
 impl Field {
-    pub fn new(width: u16, height: u16) -> Field {
-        let bit_set = FixedBitSet::with_capacity(width as usize * height as usize);
+    pub fn new(width: u32, height: u32) -> Field {
         Field {
             width,
             height,
-            bit_set,
+            wormholes: Vec::new(),
+            chunks: BTreeMap::new(),
+            filled: 0,
         }
     }
-    fn to_idx(&self, p: &FieldPoint) -> u16 {
-        (p.y * self.width) + p.x
+    pub fn len(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn columns(&self) -> u32 {
+        self.width.div_ceil(CHUNK)
     }
-    pub fn from_idx(&self, idx: u16) -> FieldPoint {
+    pub fn rows(&self) -> u32 {
+        self.height.div_ceil(CHUNK)
+    }
+    /// Chunks on the right and bottom edges are cut short by the field
+    fn chunk_size(&self, (x, y): ChunkId) -> (u32, u32) {
+        let width = (self.width - x * CHUNK).min(CHUNK);
+        let height = (self.height - y * CHUNK).min(CHUNK);
+        (width, height)
+    }
+    pub fn from_idx(&self, idx: u64) -> FieldPoint {
         FieldPoint {
-            x: idx % self.width,
-            y: idx / self.width,
+            x: (idx % self.width as u64) as u32,
+            y: (idx / self.width as u64) as u32,
         }
     }
+    /// Either a snake, a wall or a portal is there
     pub fn filled(&self, p: &FieldPoint) -> bool {
-        self.idx_filled(self.to_idx(p))
+        self.chunks
+            .get(&chunk_of(p))
+            .is_some_and(|chunk| chunk.filled(local_idx(p)))
+    }
+    pub fn idx_filled(&self, idx: u64) -> bool {
+        self.filled(&self.from_idx(idx))
+    }
+    pub fn free_count(&self) -> u64 {
+        self.len() - self.filled
+    }
+    /// Cells taken by snake bodies
+    pub fn snake_count(&self) -> u64 {
+        let chunks = self.chunks.values();
+        chunks.map(|chunk| chunk.snakes.count_ones(..) as u64).sum()
     }
-    pub fn idx_filled(&self, idx: u16) -> bool {
-        self.bit_set.contains(idx.into())
+    /// Every free cell once, starting from a random chunk and a random cell in each chunk
+    pub fn free_cells(&self, rng: &mut Rng) -> impl Iterator<Item = FieldPoint> + '_ {
+        let columns = self.columns() as u64;
+        let count = columns * self.rows() as u64;
+        let start = rng.next_u64() % count.max(1);
+        let offset = rng.next_u64();
+        (0..count).flat_map(move |i| {
+            let n = (start + i) % count;
+            let id = ((n % columns) as u32, (n / columns) as u32);
+            let origin = (id.0 * CHUNK, id.1 * CHUNK);
+            let cells: Box<dyn Iterator<Item = FieldPoint>> = match self.chunks.get(&id) {
+                // same order as an untouched chunk, whatever the chunk went through
+                Some(chunk) => {
+                    let free = chunk.free();
+                    let n = (offset % free.len().max(1) as u64) as usize;
+                    Box::new(free.from_nth(n).map(move |idx| {
+                        let idx = idx as u32;
+                        FieldPoint {
+                            x: origin.0 + idx % CHUNK,
+                            y: origin.1 + idx / CHUNK,
+                        }
+                    }))
+                }
+                // nothing ever landed on it
+                None => {
+                    let (width, height) = self.chunk_size(id);
+                    let len = width as u64 * height as u64;
+                    Box::new((0..len).map(move |i| {
+                        let idx = (offset.wrapping_add(i) % len) as u32;
+                        FieldPoint {
+                            x: origin.0 + idx % width,
+                            y: origin.1 + idx / width,
+                        }
+                    }))
+                }
+            };
+            cells
+        })
+    }
+    pub fn cell(&self, p: &FieldPoint) -> Cell {
+        let Some(chunk) = self.chunks.get(&chunk_of(p)) else {
+            return Cell::Empty;
+        };
+        let idx = local_idx(p);
+        if chunk.walls.contains(idx) {
+            Cell::Wall
+        } else if chunk.portals.contains(idx) {
+            Cell::Portal
+        } else if chunk.snakes.contains(idx) {
+            Cell::Snake
+        } else {
+            Cell::Empty
+        }
     }
     pub fn set(&mut self, p: &FieldPoint, enabled: bool) {
-        self.bit_set.set(self.to_idx(p).into(), enabled)
+        self.update(p, enabled, |chunk| &mut chunk.snakes);
+    }
+    pub fn set_wall(&mut self, p: &FieldPoint, enabled: bool) {
+        self.update(p, enabled, |chunk| &mut chunk.walls);
+    }
+    pub fn walls(&self) -> Vec<FieldPoint> {
+        let mut walls: Vec<FieldPoint> = self
+            .chunks
+            .iter()
+            .flat_map(|((x, y), chunk)| {
+                chunk.walls.ones().map(move |idx| FieldPoint {
+                    x: x * CHUNK + idx as u32 % CHUNK,
+                    y: y * CHUNK + idx as u32 / CHUNK,
+                })
+            })
+            .collect();
+        walls.sort_by_key(|p| (p.y, p.x));
+        walls
+    }
+    pub fn add_wormhole(&mut self, wormhole: Wormhole) {
+        self.update(&wormhole.0, true, |chunk| &mut chunk.portals);
+        self.update(&wormhole.1, true, |chunk| &mut chunk.portals);
+        self.wormholes.push(wormhole);
+    }
+    pub fn clear_snakes(&mut self) {
+        self.clear(|chunk| &mut chunk.snakes);
+    }
+    pub fn clear_walls(&mut self) {
+        self.clear(|chunk| &mut chunk.walls);
+    }
+    pub fn clear_wormholes(&mut self) {
+        self.clear(|chunk| &mut chunk.portals);
+        self.wormholes.clear();
+    }
+    fn update(&mut self, p: &FieldPoint, enabled: bool, layer: fn(&mut Chunk) -> &mut FixedBitSet) {
+        let id = chunk_of(p);
+        if !enabled && !self.chunks.contains_key(&id) {
+            return;
+        }
+        let (width, height) = self.chunk_size(id);
+        let chunk = self
+            .chunks
+            .entry(id)
+            .or_insert_with(|| Chunk::new(width, height));
+        let idx = local_idx(p);
+        let was_filled = chunk.filled(idx);
+        layer(chunk).set(idx, enabled);
+        match (was_filled, chunk.refresh(idx)) {
+            (false, true) => self.filled += 1,
+            (true, false) => self.filled -= 1,
+            _ => (),
+        }
+    }
+    fn clear(&mut self, layer: fn(&mut Chunk) -> &mut FixedBitSet) {
+        for chunk in self.chunks.values_mut() {
+            layer(chunk).clear();
+            chunk.refresh_all();
+        }
+        self.filled = self.chunks.values().map(Chunk::filled_count).sum();
     }
 }
 
 #[repr(C)]
-#[derive(bincode::Encode, bincode::Decode, Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(bincode::Encode, bincode::Decode, Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub struct FieldPoint {
-    pub x: u16,
-    pub y: u16,
+    pub x: u32,
+    pub y: u32,
 }
 
 impl FieldPoint {
-    pub fn add_tuple(&self, tuple: (u16, u16)) -> FieldPoint {
+    pub fn add_tuple(&self, tuple: (u32, u32)) -> FieldPoint {
         let (x, y) = (self.x + tuple.0, self.y + tuple.1);
         FieldPoint { x, y }
     }
@@ -122,6 +294,30 @@ impl FieldPoint {
         };
         FieldPoint { x, y }
     }
+    /// Same as `wrapping_add`, but `None` when going through an edge that doesn't wrap
+    pub fn bounded_add(&self, direction: WrappableDirection) -> Option<FieldPoint> {
+        if direction.crosses_edge(self) {
+            return None;
+        }
+        Some(self.wrapping_add(direction))
+    }
+    /// Same as `bounded_add`, but stepping into a portal comes out past the other end
+    pub fn warping_add(
+        &self,
+        direction: WrappableDirection,
+        wormholes: &[Wormhole],
+    ) -> Option<FieldPoint> {
+        let next = self.bounded_add(direction)?;
+        let exit = wormholes.iter().find_map(|(a, b)| match next {
+            p if p.eq(a) => Some(*b),
+            p if p.eq(b) => Some(*a),
+            _ => None,
+        });
+        match exit {
+            Some(exit) => exit.bounded_add(direction),
+            None => Some(next),
+        }
+    }
     pub fn wrapping_sub(&self, direction: WrappableDirection) -> FieldPoint {
         let mut direction = direction;
         direction.to = opposite_of(direction.to);
@@ -137,6 +333,21 @@ pub enum Direction {
     Down,
     Left,
 }
+
+impl FromStr for Direction {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "up" => Ok(Direction::Up),
+            "right" => Ok(Direction::Right),
+            "down" => Ok(Direction::Down),
+            "left" => Ok(Direction::Left),
+            _ => Err(()),
+        }
+    }
+}
+
 pub fn opposite_of(direction: Direction) -> Direction {
     match direction {
         Direction::Left => Direction::Right,
@@ -145,18 +356,121 @@ pub fn opposite_of(direction: Direction) -> Direction {
         Direction::Down => Direction::Up,
     }
 }
-#[derive(bincode::Encode, bincode::Decode, Debug, Copy, Clone)]
+/// Which field edges a snake goes through to come out on the other side. Solid edges kill.
+/// `Horizontal` wraps around the left and right edges, `Vertical` around top and bottom
+#[derive(bincode::Encode, bincode::Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Both,
+    Horizontal,
+    Vertical,
+    None,
+}
+
+impl Wrap {
+    pub fn wraps_x(&self) -> bool {
+        matches!(self, Wrap::Both | Wrap::Horizontal)
+    }
+    pub fn wraps_y(&self) -> bool {
+        matches!(self, Wrap::Both | Wrap::Vertical)
+    }
+}
+
+impl FromStr for Wrap {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "both" | "true" => Ok(Wrap::Both),
+            "none" | "false" => Ok(Wrap::None),
+            "horizontal" => Ok(Wrap::Horizontal),
+            "vertical" => Ok(Wrap::Vertical),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(bincode::Encode, bincode::Decode, Debug, Copy, Clone, PartialEq)]
 pub struct WrappableDirection {
     pub to: Direction,
     pub max: FieldPoint,
+    pub wrap: Wrap,
+}
+
+impl WrappableDirection {
+    /// Whether a step from `p` goes through an edge that doesn't wrap
+    pub fn crosses_edge(&self, p: &FieldPoint) -> bool {
+        match self.to {
+            Direction::Right => !self.wrap.wraps_x() && p.x + 1 >= self.max.x,
+            Direction::Left => !self.wrap.wraps_x() && p.x == 0,
+            Direction::Down => !self.wrap.wraps_y() && p.y + 1 >= self.max.y,
+            Direction::Up => !self.wrap.wraps_y() && p.y == 0,
+        }
+    }
 }
 
-#[derive(bincode::Encode, bincode::Decode, Clone, Debug)]
+/// Who gets a food reached by more than one head on the same tick
+#[derive(bincode::Encode, bincode::Decode, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FoodContest {
+    Nobody,
+    Longer,
+}
+
+#[derive(bincode::Encode, bincode::Decode, Clone, Debug, PartialEq)]
 pub struct GameConfig {
     pub size: u16,
-    pub start: (u16, u16),
-    pub dim: (u16, u16),
+    pub start: (u32, u32),
+    pub dim: (u32, u32),
     pub direction: Direction,
+    pub food_contest: FoodContest,
+    pub wrap: Wrap,
+    pub seed: u64,
+    pub map: Option<Map>,
+    pub food: FoodRules,
+}
+
+impl GameConfig {
+    /// Takes dimensions from the map, which also lays out walls and spawn points
+    pub fn with_map(self, map: Map) -> GameConfig {
+        GameConfig {
+            dim: map.dim,
+            wrap: map.wrap,
+            map: Some(map),
+            ..self
+        }
+    }
+
+    pub fn contains(&self, p: &FieldPoint) -> bool {
+        let (width, height) = self.dim;
+        p.x < width && p.y < height
+    }
+
+    /// Configs decoded from elsewhere are checked before a game takes them
+    pub fn validate(&self) -> Result<(), Error> {
+        let (width, height) = self.dim;
+        if width == 0 || height == 0 {
+            return invalid(format!("field of {width}x{height}"));
+        }
+        if self.size == 0 || self.food.special_size == 0 {
+            return invalid("snakes and specials take at least a cell");
+        }
+        // snakes hatch in a straight line
+        if self.size as u32 > width.max(height) {
+            return invalid(format!("snakes of {} don't fit in the field", self.size));
+        }
+        let (x, y) = self.start;
+        if !self.contains(&FieldPoint { x, y }) {
+            return invalid("start out of the field");
+        }
+        if let Some(map) = &self.map {
+            let spawns = map.spawns.iter().map(|spawn| &spawn.position);
+            let wormholes = map.wormholes.iter().flat_map(|(a, b)| [a, b]);
+            let mut cells = map.walls.iter().chain(spawns).chain(wormholes);
+            if map.dim != self.dim || !cells.all(|p| self.contains(p)) {
+                return invalid(format!("map '{}' out of the field", map.name));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for GameConfig {
@@ -166,6 +480,11 @@ impl Default for GameConfig {
             start: (4, 3),
             dim: (15, 10),
             direction: Direction::Right,
+            food_contest: FoodContest::Nobody,
+            wrap: Wrap::Both,
+            seed: 0,
+            map: None,
+            food: FoodRules::DEFAULT,
         }
     }
 }
@@ -3,10 +3,14 @@ use bincode::{
     error::{self, EncodeError},
 };
 
+/// Memory a decoded value may take. Lengths are checked against it before allocating, so a
+/// few bytes claiming a huge vector fail rather than abort
+pub const DECODE_LIMIT: usize = 64 * 1024 * 1024;
+
 pub fn encode<E: enc::Encode>(val: E) -> Result<Vec<u8>, EncodeError> {
     encode_to_vec(val, config::standard())
 }
 
 pub fn decode<D: de::Decode>(src: &[u8]) -> Result<(D, usize), error::DecodeError> {
-    decode_from_slice(src, config::standard())
+    decode_from_slice(src, config::standard().with_limit::<DECODE_LIMIT>())
 }
@@ -0,0 +1,133 @@
+//! Inputs that once crashed a fuzz target, see `fuzz/regressions`. Each one goes through what
+//! its target does with it, which must not panic.
+
+use std::{fs, path::PathBuf};
+
+use snake::{
+    delta::DeltaDecoder,
+    game::{Game, GameData},
+    interest::Interest,
+    protocol::{ClientMessage, ServerMessage},
+    replay::{Replay, ReplayData},
+    types::{Direction, FieldPoint, GameConfig, GameState},
+    utils::decode,
+};
+
+/// Every input kept for `target`
+fn inputs(target: &str) -> Vec<Vec<u8>> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../fuzz/regressions")
+        .join(target);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+        .collect()
+}
+
+#[test]
+fn game_data() {
+    for bytes in inputs("game_data") {
+        let Ok((data, _size)) = decode::<GameData>(&bytes) else {
+            continue;
+        };
+        if data.validate().is_err() {
+            continue;
+        }
+        let (width, height) = data.config.dim;
+        let center = FieldPoint {
+            x: width / 2,
+            y: height / 2,
+        };
+        Interest::new(data, (64, 48), (8, 4)).view(None, &center);
+    }
+}
+
+#[test]
+fn set_game_data() {
+    for bytes in inputs("set_game_data") {
+        let mut game = Game::new(GameConfig::default());
+        if game.set_game_data(bytes).is_err() {
+            continue;
+        }
+        game.state = GameState::Playing;
+        let _ = game.add_snake();
+        let ids: Vec<u16> = game.snakes.keys().copied().collect();
+        for (tick, id) in ids.iter().cycle().take(20).enumerate() {
+            if tick % 3 == 0 {
+                game.head_to(*id, Direction::Up);
+            }
+            game.tick();
+        }
+        if let Some(id) = ids.first() {
+            game.remove_snake(*id);
+        }
+        let mut game = game.rematch(game.config.seed.wrapping_add(1));
+        game.tick();
+    }
+}
+
+/// Has to get past validation to reach the food count it was kept for, which wraps around
+/// instead of overflowing once the next food is placed
+#[test]
+fn food_count_overflow() {
+    let bytes = fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../fuzz/regressions/set_game_data/food-count-overflow"),
+    )
+    .unwrap();
+    let mut game = Game::new(GameConfig::default());
+    game.set_game_data(bytes).unwrap();
+    assert_eq!(game.food.count, u16::MAX);
+    assert!(game.food.foods.len() < game.food.minimum as usize);
+    game.state = GameState::Playing;
+    game.tick();
+    assert_eq!(game.food.count, 0);
+}
+
+#[test]
+fn client_message() {
+    for bytes in inputs("client_message") {
+        let _ = ClientMessage::from_bytes(&bytes);
+    }
+}
+
+#[test]
+fn frames() {
+    for bytes in inputs("frames") {
+        let mut decoder = DeltaDecoder::default();
+        let mut rest = &bytes[..];
+        while let Ok((message, size)) = decode::<ServerMessage>(rest) {
+            if let ServerMessage::Frame(frame) = message {
+                let _ = decoder.apply(frame);
+            }
+            if size == 0 {
+                break;
+            }
+            rest = &rest[size..];
+        }
+    }
+}
+
+#[test]
+fn replay() {
+    for bytes in inputs("replay") {
+        let Ok(data) = ReplayData::from_bytes(&bytes) else {
+            continue;
+        };
+        let Ok(mut replay) = Replay::new(data) else {
+            continue;
+        };
+        while replay.position() < 200 && replay.step().unwrap_or(false) {}
+        let _ = replay.seek(0);
+    }
+}
+
+/// A missing directory would make the tests above pass on nothing
+#[test]
+fn inputs_are_found() {
+    for target in ["game_data", "set_game_data", "frames", "replay"] {
+        assert!(!inputs(target).is_empty(), "no inputs for {target}");
+    }
+}
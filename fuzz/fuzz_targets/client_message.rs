@@ -0,0 +1,9 @@
+//! What the server reads off each websocket frame
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snake::protocol::ClientMessage;
+
+fuzz_target!(|bytes: &[u8]| {
+    let _ = ClientMessage::from_bytes(bytes);
+});
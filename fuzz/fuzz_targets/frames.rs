@@ -0,0 +1,19 @@
+//! A stream of server messages, as the browser applies them
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snake::{delta::DeltaDecoder, protocol::ServerMessage, utils::decode};
+
+fuzz_target!(|bytes: &[u8]| {
+    let mut decoder = DeltaDecoder::default();
+    let mut rest = bytes;
+    while let Ok((message, size)) = decode::<ServerMessage>(rest) {
+        if let ServerMessage::Frame(frame) = message {
+            let _ = decoder.apply(frame);
+        }
+        if size == 0 {
+            break;
+        }
+        rest = &rest[size..];
+    }
+});
@@ -0,0 +1,21 @@
+//! `GameData` as it comes from the server, cut into a view when it's valid
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snake::{game::GameData, interest::Interest, types::FieldPoint, utils::decode};
+
+fuzz_target!(|bytes: &[u8]| {
+    let Ok((data, _size)) = decode::<GameData>(bytes) else {
+        return;
+    };
+    if data.validate().is_err() {
+        return;
+    }
+    let (width, height) = data.config.dim;
+    let center = FieldPoint {
+        x: width / 2,
+        y: height / 2,
+    };
+    let interest = Interest::new(data, (64, 48), (8, 4));
+    interest.view(None, &center);
+});
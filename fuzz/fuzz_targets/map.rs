@@ -0,0 +1,24 @@
+//! Map files, played for a few ticks when they make a valid game
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snake::{game::Game, map::Map, types::GameConfig};
+
+fuzz_target!(|bytes: &[u8]| {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return;
+    };
+    let Ok(map) = Map::parse(text) else {
+        return;
+    };
+    let config = GameConfig::default().with_map(map);
+    if config.validate().is_err() {
+        return;
+    }
+    let mut game = Game::new(config);
+    let _ = game.add_snake();
+    let _ = game.add_snake();
+    for _ in 0..10 {
+        game.tick();
+    }
+});
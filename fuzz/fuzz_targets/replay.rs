@@ -0,0 +1,17 @@
+//! Replay files, played through and rewound
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snake::replay::{Replay, ReplayData};
+
+fuzz_target!(|bytes: &[u8]| {
+    let Ok(data) = ReplayData::from_bytes(bytes) else {
+        return;
+    };
+    let Ok(mut replay) = Replay::new(data) else {
+        return;
+    };
+    // long replays would only slow the fuzzer down
+    while replay.position() < 200 && replay.step().unwrap_or(false) {}
+    let _ = replay.seek(0);
+});
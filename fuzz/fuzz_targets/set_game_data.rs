@@ -0,0 +1,29 @@
+//! A game resumed from untrusted bytes, then played for a few ticks
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use snake::{
+    game::Game,
+    types::{Direction, GameConfig, GameState},
+};
+
+fuzz_target!(|bytes: &[u8]| {
+    let mut game = Game::new(GameConfig::default());
+    if game.set_game_data(bytes.to_vec()).is_err() {
+        return;
+    }
+    game.state = GameState::Playing;
+    let _ = game.add_snake();
+    let ids: Vec<u16> = game.snakes.keys().copied().collect();
+    for (tick, id) in ids.iter().cycle().take(20).enumerate() {
+        if tick % 3 == 0 {
+            game.head_to(*id, Direction::Up);
+        }
+        game.tick();
+    }
+    if let Some(id) = ids.first() {
+        game.remove_snake(*id);
+    }
+    let mut game = game.rematch(game.config.seed.wrapping_add(1));
+    game.tick();
+});
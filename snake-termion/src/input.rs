@@ -11,7 +11,8 @@ use termion::input::TermRead;
 pub fn read(game_arc: Arc<RwLock<Game>>) {
     let snake_id = RwLock::write(&game_arc)
         .expect("can't add snake")
-        .add_snake();
+        .add_snake()
+        .expect("no room for the snake");
     let mut stdin = stdin().lock().keys();
 
     loop {
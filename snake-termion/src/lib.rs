@@ -1,3 +1,4 @@
 pub mod input;
 pub mod render;
+pub mod replay;
 pub mod ticker;
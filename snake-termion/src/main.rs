@@ -1,14 +1,53 @@
+use snake::food::FoodRules;
 use snake::game::*;
+use snake::map::Map;
+use snake::replay::{Replay, ReplayData};
+use snake::rng::random_seed;
 use snake::types::GameConfig;
 use snake_termion::input;
 use snake_termion::render::TermionRender;
+use snake_termion::replay;
 use snake_termion::ticker;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::thread;
 
 fn main() {
-    let game = Game::new(GameConfig::default());
+    if let Some(path) = arg_value("--replay") {
+        let data = ReplayData::load(&path).expect("can't load replay");
+        replay::run(Replay::new(data).expect("can't start replay"));
+
+        let mut render = TermionRender::default();
+        render.show_cursor();
+        return;
+    }
+
+    let seed = arg_value("--seed")
+        .map(|seed| seed.parse().expect("--seed must be a number"))
+        .unwrap_or_else(random_seed);
+    let mut config = GameConfig {
+        seed,
+        ..GameConfig::default()
+    };
+    if let Some(path) = arg_value("--map") {
+        let text = std::fs::read_to_string(&path).expect("can't read map");
+        let map = Map::parse(&text).unwrap_or_else(|err| panic!("{path}: {err}"));
+        config = config.with_map(map);
+    }
+    if let Some(path) = arg_value("--food") {
+        let text = std::fs::read_to_string(&path).expect("can't read food rules");
+        config.food = FoodRules::parse(&text).unwrap_or_else(|err| panic!("{path}: {err}"));
+    }
+    if let Some(wrap) = arg_value("--wrap") {
+        config.wrap = wrap
+            .parse()
+            .expect("--wrap must be both, none, horizontal or vertical");
+    }
+    let mut game = Game::new(config);
+    let record = arg_value("--record");
+    if record.is_some() {
+        game.start_recording();
+    }
 
     let game_arc = Arc::new(RwLock::new(game));
     let mut handles = vec![];
@@ -25,4 +64,17 @@ fn main() {
 
     let mut render = TermionRender::default();
     render.show_cursor();
+
+    if let Some(path) = record {
+        let mut game = RwLock::write(&game_arc).expect("can't stop recording");
+        if let Some(data) = game.stop_recording() {
+            data.save(path).expect("can't save replay");
+        }
+    }
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
 }
@@ -4,7 +4,7 @@ use snake::{
     food::FoodField,
     render::GameRender,
     snake::{Snake, SnakeNode},
-    types::{Direction, FieldPoint, Food, FoodType},
+    types::{Direction, FieldPoint, Food, FoodType, GameConfig},
 };
 use termion::{
     raw::{IntoRawMode, RawTerminal},
@@ -14,6 +14,7 @@ use termion::{
 pub struct TermionRender {
     screen: AlternateScreen<RawTerminal<Stdout>>,
     tail: Option<SnakeNode>,
+    status_row: u16,
 }
 
 impl GameRender for TermionRender {
@@ -51,6 +52,14 @@ impl GameRender for TermionRender {
     fn score(&mut self, _score: u16) {
         // coming soon...
     }
+
+    fn wall(&mut self, p: &FieldPoint) {
+        write_point("#", p, &mut self.screen);
+    }
+
+    fn portal(&mut self, p: &FieldPoint) {
+        write_point("O", p, &mut self.screen);
+    }
 }
 impl Default for TermionRender {
     fn default() -> Self {
@@ -61,13 +70,48 @@ impl Default for TermionRender {
             .unwrap();
         let mut screen = stdout;
         write!(screen, "{}{}", termion::cursor::Hide, ToAlternateScreen).unwrap();
-        TermionRender { screen, tail: None }
+        TermionRender {
+            screen,
+            tail: None,
+            status_row: 1,
+        }
     }
 }
 impl TermionRender {
     pub fn clear(&mut self) {
         write!(self.screen, "{}", termion::clear::All).unwrap();
     }
+    /// Draws the edges that don't wrap around the field, the status line goes right below
+    pub fn border(&mut self, config: &GameConfig) {
+        let (width, height) = (terminal(config.dim.0), terminal(config.dim.1));
+        let (left, top) = (X_OFFSET - 1, Y_OFFSET - 1);
+        let (right, bottom) = (width + X_OFFSET, height + Y_OFFSET);
+        if !config.wrap.wraps_y() {
+            for x in left..=right {
+                write_at("-", x, top, &mut self.screen);
+                write_at("-", x, bottom, &mut self.screen);
+            }
+        }
+        if !config.wrap.wraps_x() {
+            for y in top..=bottom {
+                write_at("|", left, y, &mut self.screen);
+                write_at("|", right, y, &mut self.screen);
+            }
+        }
+        self.status_row = bottom + 1;
+    }
+    /// Writes a line of text below the field
+    pub fn status(&mut self, text: &str) {
+        write!(
+            self.screen,
+            "{}{}{}",
+            termion::cursor::Goto(1, self.status_row),
+            termion::clear::CurrentLine,
+            text
+        )
+        .unwrap();
+        self.screen.flush().unwrap();
+    }
     pub fn show_cursor(&mut self) {
         write!(self.screen, "{}", termion::cursor::Show).unwrap();
         self.screen.flush().unwrap();
@@ -135,13 +179,20 @@ pub fn write(c: &str, node: &SnakeNode, screen: &mut AlternateScreen<RawTerminal
 }
 
 pub fn write_point(c: &str, point: &FieldPoint, screen: &mut AlternateScreen<RawTerminal<Stdout>>) {
-    let x = point.x;
-    let y = point.y;
-    write!(
+    let (x, y) = (terminal(point.x), terminal(point.y));
+    write_at(
+        c,
+        x.saturating_add(X_OFFSET),
+        y.saturating_add(Y_OFFSET),
         screen,
-        "{}{}",
-        termion::cursor::Goto(x + X_OFFSET, y + Y_OFFSET),
-        c
-    )
-    .unwrap();
+    );
+}
+
+/// Terminals are way smaller than the largest fields, what goes past them is left out
+fn terminal(n: u32) -> u16 {
+    n.min(u16::MAX as u32) as u16
+}
+
+fn write_at(c: &str, x: u16, y: u16, screen: &mut AlternateScreen<RawTerminal<Stdout>>) {
+    write!(screen, "{}{}", termion::cursor::Goto(x, y), c).unwrap();
 }
@@ -0,0 +1,87 @@
+use std::{
+    io::stdin,
+    sync::mpsc::{channel, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use snake::replay::Replay;
+use termion::{event::Key, input::TermRead};
+
+use crate::render::TermionRender;
+
+const TICK: Duration = Duration::from_millis(500);
+const SEEK_TICKS: isize = 25;
+
+enum Control {
+    Pause,
+    Step(isize),
+    Start,
+    Quit,
+}
+
+/// Plays a replay back. Space pauses, left/right steps one tick, up/down seeks
+/// a few ticks, `0` goes back to the start and `q` quits. Playback stops where it no longer
+/// follows the recording.
+pub fn run(mut replay: Replay) {
+    let (tx, rx) = channel();
+    thread::spawn(move || read(tx));
+
+    let mut render = TermionRender::default();
+    let mut paused = false;
+    let mut diverged = false;
+    loop {
+        render.clear();
+        render.border(&replay.game.config);
+        replay.game.draw(&mut render);
+        let state = match (diverged, paused) {
+            (true, _) => "diverged",
+            (false, true) => "paused",
+            (false, false) => "playing",
+        };
+        render.status(&format!(
+            "tick {}/{} {state}",
+            replay.position(),
+            replay.len()
+        ));
+
+        let played = match rx.recv_timeout(TICK) {
+            Ok(Control::Quit) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(Control::Pause) => {
+                paused = !paused;
+                continue;
+            }
+            Ok(Control::Start) => replay.seek(0),
+            Ok(Control::Step(ticks)) => {
+                paused = true;
+                let position = replay.position().saturating_add_signed(ticks);
+                replay.seek(position)
+            }
+            Err(RecvTimeoutError::Timeout) if paused => continue,
+            Err(RecvTimeoutError::Timeout) => replay.step().map(|more| paused = !more),
+        };
+        diverged = played.is_err();
+        if diverged {
+            paused = true;
+        }
+    }
+}
+
+fn read(tx: Sender<Control>) {
+    for key in stdin().lock().keys() {
+        let control = match key.unwrap() {
+            Key::Char('q') | Key::Esc => Control::Quit,
+            Key::Char(' ') => Control::Pause,
+            Key::Char('0') => Control::Start,
+            Key::Right => Control::Step(1),
+            Key::Left => Control::Step(-1),
+            Key::Up => Control::Step(SEEK_TICKS),
+            Key::Down => Control::Step(-SEEK_TICKS),
+            _ => continue,
+        };
+        let quit = matches!(control, Control::Quit);
+        if tx.send(control).is_err() || quit {
+            break;
+        }
+    }
+}
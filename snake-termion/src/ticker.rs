@@ -7,25 +7,33 @@ use std::{
 
 use crate::render::TermionRender;
 
+const TICK_MILLIS: u128 = 500;
+
 pub fn run(game: Arc<RwLock<Game>>) {
-    RwLock::write(&game).expect("can't add food").add_food();
+    let tick = RwLock::read(&game)
+        .expect("can't read config")
+        .config
+        .map
+        .as_ref()
+        .and_then(|map| map.tick)
+        .map_or(TICK_MILLIS, |tick| tick as u128);
     let mut render = TermionRender::default();
     loop {
         let now = Instant::now();
 
         let mut game = RwLock::write(&game).expect("cant move");
         game.tick();
-        game.add_missing_food();
 
         render.clear();
+        render.border(&game.config);
         game.draw(&mut render);
 
         if game.state == GameState::Quit {
             break;
         }
-        let sleep = (500 - now.elapsed().as_millis()) as u64;
-        if sleep > 0 {
-            thread::sleep(Duration::from_millis(sleep));
+        // a frame slower than the tick goes straight to the next one
+        if let Some(sleep) = tick.checked_sub(now.elapsed().as_millis()) {
+            thread::sleep(Duration::from_millis(sleep as u64));
         }
     }
 }
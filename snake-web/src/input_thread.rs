@@ -1,56 +1,110 @@
+use crate::rooms::Seat;
+use crate::websocket_game::{hang_up, Connection};
 use futures_util::stream::SplitStream;
 use futures_util::StreamExt;
 use salvo::websocket::WebSocket;
-use snake::{game::Game, types::Direction, utils::decode};
-use std::{sync::Arc, time::SystemTime};
+use snake::{game::Game, protocol::ClientMessage};
+use std::{
+    ops::Bound::{Excluded, Unbounded},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use tokio::sync::RwLock;
-use tracing::{instrument, Span};
+use tracing::{instrument, warn, Span};
 
-use crate::{websocket_game::DIRECTION, websocket_game::PING};
+/// Frames that don't decode before the client is let go
+const MAX_BAD_FRAMES: u8 = 8;
 
 #[instrument(skip_all)]
-pub fn rx_commands(snake_id: u16, mut rx: SplitStream<WebSocket>, game: Arc<RwLock<Game>>) {
+pub fn rx_commands(mut rx: SplitStream<WebSocket>, connection: Arc<Connection>, seat: Arc<Seat>) {
     let fut = async move {
+        let room = &seat.room;
+        let mut bad_frames = 0;
         while let Some(result) = rx.next().await {
             let now = SystemTime::now();
             match result {
                 Err(_msg) => break,
                 Ok(msg) => {
-                    let msg = msg.as_bytes();
-                    if msg.is_empty() {
+                    if msg.is_close() {
+                        break;
+                    }
+                    // websocket pings and pongs are answered underneath
+                    if !msg.is_binary() {
                         continue;
                     }
-                    match msg {
-                        [PING, ..] => log_ping(msg, now, snake_id),
-                        [DIRECTION, code] => {
-                            if let Some(next_direction) = to_direction(*code) {
-                                RwLock::write(&game).await.head_to(snake_id, next_direction);
+                    let snake_id = connection.snake_id();
+                    let message = match ClientMessage::from_bytes(msg.as_bytes()) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            warn!(?snake_id, "dropping frame: {err}");
+                            bad_frames += 1;
+                            if bad_frames >= MAX_BAD_FRAMES {
+                                warn!(?snake_id, "too many bad frames, disconnecting");
+                                break;
                             }
+                            continue;
+                        }
+                    };
+                    match (message, snake_id) {
+                        (ClientMessage::Pong(sent), _) => log_ping(sent, now, snake_id),
+                        (ClientMessage::Turn(to), Some(snake_id)) => {
+                            RwLock::write(&room.game).await.head_to(snake_id, to);
+                        }
+                        (ClientMessage::Keyframe, _) => {
+                            connection.resync.store(true, Ordering::Relaxed);
+                        }
+                        (ClientMessage::Ready, Some(snake_id)) => {
+                            RwLock::write(&room.round).await.ready(snake_id);
                         }
+                        (ClientMessage::Join, None) => {
+                            connection.joining.store(true, Ordering::Relaxed);
+                        }
+                        (ClientMessage::FollowNext, None) => {
+                            follow(&room.game, &connection, true).await;
+                        }
+                        (ClientMessage::FollowPrevious, None) => {
+                            follow(&room.game, &connection, false).await;
+                        }
+                        // spectators don't turn, players don't follow
                         _ => continue,
                     }
                 }
             }
         }
-        RwLock::write(&game).await.remove_snake(snake_id);
+        hang_up(&connection, &seat).await;
     };
     tokio::task::spawn(fut);
 }
 
-#[instrument(fields(ping_ms, ping_μs) skip(msg, now))]
-fn log_ping(msg: &[u8], now: SystemTime, snake_id: u16) {
-    let (past, _size): (SystemTime, usize) = decode(&msg[1..msg.len()]).unwrap();
-    let duration = now.duration_since(past).expect("ping measure error");
-    Span::current().record("ping_ms", duration.as_millis());
-    Span::current().record("ping_μs", duration.as_micros());
+/// Moves a spectator on to the next snake by id, or the previous one, going around
+async fn follow(game: &RwLock<Game>, connection: &Connection, forward: bool) {
+    let game = RwLock::read(game).await;
+    let snakes = &game.snakes;
+    let current = connection.following.load(Ordering::Relaxed);
+    let next = match forward {
+        true => snakes
+            .range((Excluded(current), Unbounded))
+            .next()
+            .or_else(|| snakes.iter().next()),
+        false => snakes
+            .range(..current)
+            .next_back()
+            .or_else(|| snakes.iter().next_back()),
+    };
+    if let Some((id, _snake)) = next {
+        connection.following.store(*id, Ordering::Relaxed);
+    }
 }
 
-fn to_direction(msg: u8) -> Option<Direction> {
-    match msg {
-        0 => Some(Direction::Left),
-        1 => Some(Direction::Up),
-        2 => Some(Direction::Right),
-        3 => Some(Direction::Down),
-        _ => None,
-    }
+#[instrument(fields(ping_ms, ping_μs) skip(sent, now))]
+fn log_ping(sent: u64, now: SystemTime, snake_id: Option<u16>) {
+    // the client echoes whatever it wants, a pong from the future is ignored
+    let Some(past) = UNIX_EPOCH.checked_add(Duration::from_micros(sent)) else {
+        return;
+    };
+    let Ok(duration) = now.duration_since(past) else {
+        return;
+    };
+    Span::current().record("ping_ms", duration.as_millis());
+    Span::current().record("ping_μs", duration.as_micros());
 }
@@ -3,6 +3,10 @@ use std::{collections::HashMap, sync::Arc};
 use snake::types::Direction;
 use tokio::sync::RwLock;
 pub mod input_thread;
+pub mod maps;
+pub mod outbox;
+pub mod rooms;
+pub mod sessions;
 pub mod websocket_game;
 
 pub type DirectionArc = Arc<RwLock<Direction>>;
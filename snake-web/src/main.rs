@@ -12,7 +12,7 @@ use rust_embed::RustEmbed;
 use salvo::prelude::*;
 use salvo::serve_static::static_embed;
 use salvo::websocket::WebSocketUpgrade;
-use snake_web::websocket_game::WsGame;
+use snake_web::rooms::{RoomError, Rooms, DEFAULT_ROOM};
 use tracing::level_filters::LevelFilter;
 use tracing::Level;
 use tracing_subscriber::prelude::*;
@@ -22,7 +22,7 @@ use tracing_subscriber::{self};
 #[folder = "www/"]
 struct Assets;
 
-static GAME: Lazy<WsGame> = Lazy::new(WsGame::default);
+static ROOMS: Lazy<Arc<Rooms>> = Lazy::new(|| Arc::new(Rooms::default()));
 const PORT_BIND: &str = "80";
 
 fn init_tracer() -> Tracer {
@@ -58,11 +58,13 @@ async fn main() {
         ))
     });
 
-    GAME.start_game();
     let router = Router::new()
         .hoop(affix::inject(Arc::new(tracer.clone())))
         .hoop(Tracing::new(tracer))
         .push(Router::with_path("game_data").goal(user_connected))
+        .push(Router::with_path("rooms").get(list_rooms))
+        .push(Router::with_path("rooms/<code>").get(room_info))
+        .push(Router::with_path("metrics").get(room_metrics))
         .push(Router::with_path("<*path>").get(static_embed::<Assets>().fallback("index.html")));
 
     let port = std::env::var("PORT_BIND").unwrap_or_else(|_| PORT_BIND.to_owned());
@@ -79,7 +81,46 @@ async fn user_connected(
     res: &mut Response,
     _depot: &mut Depot,
 ) -> Result<(), StatusError> {
+    let code: String = req.query("room").unwrap_or_else(|| DEFAULT_ROOM.to_owned());
+    // `spectate=1` watches the room without playing
+    let spectator = req
+        .query::<String>("spectate")
+        .is_some_and(|value| value != "0");
+    // a full room may be holding the seat of a player coming back, who gets it from the session
+    let resuming = req.query::<String>("resume").is_some();
+    let seat = match ROOMS.join(&code, spectator) {
+        Err(RoomError::Full) if resuming => ROOMS.join(&code, true),
+        joined => joined,
+    };
+    let seat = seat.map_err(|err| match err {
+        RoomError::InvalidCode => StatusError::bad_request().brief(err.to_string()),
+        RoomError::Full => StatusError::service_unavailable().brief(err.to_string()),
+    })?;
     WebSocketUpgrade::new()
-        .upgrade(req, res, |ws| GAME.ingress_user(ws))
+        .upgrade(req, res, move |ws| async move {
+            let room = Arc::clone(&seat.room);
+            room.ingress_user(ws, seat).await
+        })
         .await
 }
+
+/// Open rooms and their players, as JSON
+#[handler]
+async fn list_rooms(res: &mut Response) {
+    res.render(Json(ROOMS.open()));
+}
+
+/// One room by code, as JSON. Rooms nobody is in are not found
+#[handler]
+async fn room_info(req: &mut Request, res: &mut Response) -> Result<(), StatusError> {
+    let code: String = req.param("code").unwrap_or_default();
+    let room = ROOMS.info(&code).ok_or_else(StatusError::not_found)?;
+    res.render(Json(room));
+    Ok(())
+}
+
+/// Send queues, dropped frames and evicted clients of every room, as JSON
+#[handler]
+async fn room_metrics(res: &mut Response) {
+    res.render(Json(ROOMS.metrics()));
+}
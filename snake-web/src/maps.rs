@@ -0,0 +1,43 @@
+use std::{fs, path::Path};
+
+use snake::map::Map;
+use tracing::{error, info};
+
+/// Loads every `.map` file in `dir` sorted by file name. Broken maps are logged and skipped
+pub fn load_dir(dir: &Path) -> Vec<Map> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("can't read maps from {dir:?} {err}");
+            return Vec::new();
+        }
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "map"))
+        .collect();
+    paths.sort();
+
+    let mut maps = Vec::new();
+    for path in paths {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) => {
+                error!("can't read map {path:?} {err}");
+                continue;
+            }
+        };
+        match Map::parse(&text) {
+            Ok(mut map) => {
+                if map.name.is_empty() {
+                    let stem = path.file_stem().unwrap_or_default();
+                    map.name = stem.to_string_lossy().into_owned();
+                }
+                info!("loaded map {} from {path:?}", map.name);
+                maps.push(map);
+            }
+            Err(err) => error!("invalid map {path:?} {err}"),
+        }
+    }
+    maps
+}
@@ -0,0 +1,188 @@
+//! Messages on their way to a client. Each connection gets a bounded queue, drained into its
+//! socket by a task of its own, so a slow client can't hold up the game loop. Frames that don't
+//! fit are dropped and counted, and clients that stay behind for too long are let go.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures_util::{stream::SplitSink, SinkExt};
+use salvo::websocket::{Message, WebSocket};
+use serde::Serialize;
+use snake::protocol::ServerMessage;
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    time::{timeout, Instant},
+};
+
+/// Messages waiting for a client, unless `SEND_QUEUE` says otherwise
+pub const SEND_QUEUE: usize = 16;
+/// Time a client can stay behind before being let go, unless `EVICT_AFTER` gives the seconds
+pub const EVICT_AFTER: Duration = Duration::from_secs(5);
+
+/// Counters for every client of a room
+#[derive(Debug, Default)]
+pub struct SendStats {
+    /// Messages waiting to be sent, across all clients
+    queued: AtomicUsize,
+    /// Longest queue any client had
+    deepest: AtomicUsize,
+    dropped: AtomicU64,
+    evicted: AtomicU64,
+}
+
+/// Room counters as listed to clients
+#[derive(Debug, Serialize)]
+pub struct SendInfo {
+    pub queued: usize,
+    pub deepest: usize,
+    pub dropped_frames: u64,
+    pub evicted: u64,
+}
+
+impl SendStats {
+    pub fn info(&self) -> SendInfo {
+        SendInfo {
+            queued: self.queued.load(Ordering::Relaxed),
+            deepest: self.deepest.load(Ordering::Relaxed),
+            dropped_frames: self.dropped.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Sent {
+    Queued,
+    /// The queue was full
+    Dropped,
+}
+
+/// The socket is gone, or the client stayed behind for too long
+#[derive(Debug, PartialEq)]
+pub struct Closed;
+
+#[derive(Debug)]
+pub struct Outbox {
+    tx: mpsc::Sender<Message>,
+    stats: Arc<SendStats>,
+    /// Tells the writer to skip what's left and close the socket
+    evicted: Arc<AtomicBool>,
+    evict_after: Duration,
+    /// Since when frames are being dropped
+    behind_since: Option<Instant>,
+    /// Frames dropped for this client
+    dropped: u64,
+}
+
+impl Outbox {
+    /// Spawns the task writing queued messages into `ws_tx`
+    pub fn new(
+        mut ws_tx: SplitSink<WebSocket, Message>,
+        capacity: usize,
+        evict_after: Duration,
+        stats: Arc<SendStats>,
+    ) -> Outbox {
+        let (tx, mut rx) = mpsc::channel::<Message>(capacity.max(1));
+        let evicted = Arc::new(AtomicBool::new(false));
+        tokio::task::spawn({
+            let stats = Arc::clone(&stats);
+            let evicted = Arc::clone(&evicted);
+            async move {
+                while let Some(message) = rx.recv().await {
+                    stats.queued.fetch_sub(1, Ordering::Relaxed);
+                    if evicted.load(Ordering::Relaxed) || ws_tx.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                rx.close();
+                while rx.try_recv().is_ok() {
+                    stats.queued.fetch_sub(1, Ordering::Relaxed);
+                }
+                let _ = ws_tx.close().await;
+            }
+        });
+        Outbox {
+            tx,
+            stats,
+            evicted,
+            evict_after,
+            behind_since: None,
+            dropped: 0,
+        }
+    }
+
+    /// Queues a frame, dropping it when the queue is full. Once frames were dropped for longer
+    /// than `evict_after` the client is let go
+    pub fn push(&mut self, message: &ServerMessage) -> Result<Sent, Closed> {
+        // counted ahead, the writer may take it off before `try_send` is back
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        let sent = self.tx.try_send(Message::binary(message.to_bytes()));
+        if sent.is_err() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        match sent {
+            Ok(()) => {
+                self.deepest();
+                self.behind_since = None;
+                Ok(Sent::Queued)
+            }
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                let since = *self.behind_since.get_or_insert_with(Instant::now);
+                match since.elapsed() > self.evict_after {
+                    true => Err(self.evict()),
+                    false => Ok(Sent::Dropped),
+                }
+            }
+            Err(TrySendError::Closed(_)) => Err(Closed),
+        }
+    }
+
+    /// Queues a message the client can't do without, waiting for room as long as it may stay
+    /// behind
+    pub async fn send(&mut self, message: &ServerMessage) -> Result<(), Closed> {
+        let message = Message::binary(message.to_bytes());
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        let sent = timeout(self.evict_after, self.tx.send(message)).await;
+        if !matches!(sent, Ok(Ok(()))) {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        match sent {
+            Ok(Ok(())) => {
+                self.deepest();
+                Ok(())
+            }
+            Ok(Err(_closed)) => Err(Closed),
+            Err(_elapsed) => Err(self.evict()),
+        }
+    }
+
+    /// Frames dropped for this client
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Messages waiting for this client
+    pub fn depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    fn deepest(&self) {
+        self.stats
+            .deepest
+            .fetch_max(self.depth(), Ordering::Relaxed);
+    }
+
+    fn evict(&self) -> Closed {
+        if !self.evicted.swap(true, Ordering::Relaxed) {
+            self.stats.evicted.fetch_add(1, Ordering::Relaxed);
+        }
+        Closed
+    }
+}
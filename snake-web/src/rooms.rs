@@ -0,0 +1,254 @@
+//! Rooms players join by code, each one a game of its own with its tick loop and broadcast
+//! channel. A room opens with the first player or spectator joining it and closes when the last
+//! one leaves. Spectators don't count toward the players a room takes.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::outbox::SendInfo;
+use crate::websocket_game::{Settings, WsGame};
+
+/// Where players go when they don't ask for a room
+pub const DEFAULT_ROOM: &str = "main";
+/// Players in a room, unless `MAX_PLAYERS` says otherwise
+const MAX_PLAYERS: usize = 16;
+const MAX_CODE_LEN: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum RoomError {
+    /// Codes are up to 16 letters, digits, `-` or `_`
+    InvalidCode,
+    Full,
+}
+
+impl fmt::Display for RoomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoomError::InvalidCode => write!(f, "invalid room code"),
+            RoomError::Full => write!(f, "room is full"),
+        }
+    }
+}
+
+impl std::error::Error for RoomError {}
+
+#[derive(Debug)]
+struct Room {
+    game: Arc<WsGame>,
+    players: usize,
+    spectators: usize,
+}
+
+/// An open room as listed to clients
+#[derive(Debug, Serialize)]
+pub struct RoomInfo {
+    pub code: String,
+    pub players: usize,
+    pub spectators: usize,
+    pub max_players: usize,
+}
+
+/// How sending goes in an open room
+#[derive(Debug, Serialize)]
+pub struct RoomMetrics {
+    pub code: String,
+    pub players: usize,
+    pub spectators: usize,
+    #[serde(flatten)]
+    pub send: SendInfo,
+}
+
+#[derive(Debug)]
+pub struct Rooms {
+    settings: Settings,
+    max_players: usize,
+    rooms: Mutex<HashMap<String, Room>>,
+}
+
+impl Default for Rooms {
+    fn default() -> Self {
+        let max_players = std::env::var("MAX_PLAYERS")
+            .ok()
+            .and_then(|max| max.parse().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(MAX_PLAYERS);
+        Rooms {
+            settings: Settings::default(),
+            max_players,
+            rooms: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Rooms {
+    /// Takes a seat in the room, opening it when nobody is there. Spectators always get one
+    pub fn join(self: &Arc<Self>, code: &str, spectator: bool) -> Result<Seat, RoomError> {
+        let code = room_code(code)?;
+        let mut rooms = self.rooms.lock().unwrap();
+        let room = rooms.entry(code.clone()).or_insert_with(|| {
+            info!("opening room {code}");
+            let game = Arc::new(WsGame::new(code.clone(), &self.settings));
+            game.start_game();
+            Room {
+                game,
+                players: 0,
+                spectators: 0,
+            }
+        });
+        if spectator {
+            room.spectators += 1;
+        } else if room.players < self.max_players {
+            room.players += 1;
+        } else {
+            return Err(RoomError::Full);
+        }
+        Ok(Seat {
+            rooms: Arc::clone(self),
+            room: Arc::clone(&room.game),
+            spectator: AtomicBool::new(spectator),
+        })
+    }
+
+    /// A spectator takes a player seat, if there's one left
+    fn promote(&self, seat: &Seat) -> Result<(), RoomError> {
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(room) = rooms.get_mut(&seat.room.code) else {
+            return Ok(());
+        };
+        if !seat.is_spectator() {
+            return Ok(());
+        }
+        if room.players >= self.max_players {
+            return Err(RoomError::Full);
+        }
+        room.spectators -= 1;
+        room.players += 1;
+        seat.spectator.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Gives a player seat back for a spectator one
+    fn demote(&self, seat: &Seat) {
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(room) = rooms.get_mut(&seat.room.code) else {
+            return;
+        };
+        if seat.is_spectator() {
+            return;
+        }
+        room.players -= 1;
+        room.spectators += 1;
+        seat.spectator.store(true, Ordering::Relaxed);
+    }
+
+    fn leave(&self, seat: &Seat) {
+        let code = &seat.room.code;
+        let mut rooms = self.rooms.lock().unwrap();
+        let Some(room) = rooms.get_mut(code) else {
+            return;
+        };
+        match seat.is_spectator() {
+            true => room.spectators -= 1,
+            false => room.players -= 1,
+        }
+        if room.players + room.spectators == 0 {
+            info!("closing room {code}");
+            room.game.stop();
+            rooms.remove(code);
+        }
+    }
+
+    /// Rooms with seats left, by code
+    pub fn open(&self) -> Vec<RoomInfo> {
+        let rooms = self.rooms.lock().unwrap();
+        let mut open: Vec<RoomInfo> = rooms
+            .iter()
+            .filter(|(_code, room)| room.players < self.max_players)
+            .map(|(code, room)| RoomInfo {
+                code: code.clone(),
+                players: room.players,
+                spectators: room.spectators,
+                max_players: self.max_players,
+            })
+            .collect();
+        open.sort_by(|a, b| a.code.cmp(&b.code));
+        open
+    }
+
+    /// An open room, full or not
+    pub fn info(&self, code: &str) -> Option<RoomInfo> {
+        let code = room_code(code).ok()?;
+        let rooms = self.rooms.lock().unwrap();
+        rooms.get(&code).map(|room| RoomInfo {
+            code,
+            players: room.players,
+            spectators: room.spectators,
+            max_players: self.max_players,
+        })
+    }
+
+    /// Every open room, full or not, by code
+    pub fn metrics(&self) -> Vec<RoomMetrics> {
+        let rooms = self.rooms.lock().unwrap();
+        let mut metrics: Vec<RoomMetrics> = rooms
+            .iter()
+            .map(|(code, room)| RoomMetrics {
+                code: code.clone(),
+                players: room.players,
+                spectators: room.spectators,
+                send: room.game.send_stats.info(),
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.code.cmp(&b.code));
+        metrics
+    }
+}
+
+/// A place in a room, given back when dropped
+#[derive(Debug)]
+pub struct Seat {
+    rooms: Arc<Rooms>,
+    pub room: Arc<WsGame>,
+    spectator: AtomicBool,
+}
+
+impl Seat {
+    pub fn is_spectator(&self) -> bool {
+        self.spectator.load(Ordering::Relaxed)
+    }
+
+    /// Swaps a spectator seat for a player one
+    pub fn promote(&self) -> Result<(), RoomError> {
+        self.rooms.promote(self)
+    }
+
+    /// Back to watching, as when the promoted spectator found no room on the field
+    pub fn demote(&self) {
+        self.rooms.demote(self)
+    }
+}
+
+impl Drop for Seat {
+    fn drop(&mut self) {
+        self.rooms.leave(self);
+    }
+}
+
+/// Codes are case insensitive, `Abc` and `abc` are the same room
+fn room_code(code: &str) -> Result<String, RoomError> {
+    let code = code.trim();
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if code.is_empty() || code.len() > MAX_CODE_LEN || !code.chars().all(valid) {
+        return Err(RoomError::InvalidCode);
+    }
+    Ok(code.to_ascii_lowercase())
+}
@@ -0,0 +1,109 @@
+//! Sessions keep a snake in the game for a while after its socket drops, so a client coming
+//! back with the token it got in `Welcome` takes the same snake over. The snake keeps moving in
+//! the meantime.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use snake::{protocol::SessionToken, rng::random_seed};
+
+use tracing::warn;
+
+use crate::rooms::Seat;
+
+/// A session as a connection holds it. The generation tells apart the sockets that took the
+/// same session over, only the last one counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handle {
+    pub token: SessionToken,
+    pub generation: u32,
+}
+
+#[derive(Debug)]
+struct Session {
+    snake_id: u16,
+    generation: u32,
+    /// Keeps the room open while nobody is connected
+    seat: Option<Arc<Seat>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<SessionToken, Session>>,
+}
+
+impl Sessions {
+    pub fn open(&self, snake_id: u16) -> Handle {
+        let mut sessions = self.sessions.lock().unwrap();
+        let mut token = new_token();
+        while sessions.contains_key(&token) {
+            token = new_token();
+        }
+        let session = Session {
+            snake_id,
+            generation: 0,
+            seat: None,
+        };
+        sessions.insert(token, session);
+        Handle {
+            token,
+            generation: 0,
+        }
+    }
+
+    /// Binds the session to a new socket, giving back its snake along with the seat kept for
+    /// it, if the last socket is gone. A spectator `seat` needs either that one or a free player
+    /// seat, otherwise the session is left as it was
+    pub fn resume(
+        &self,
+        token: SessionToken,
+        seat: &Seat,
+    ) -> Option<(u16, Handle, Option<Arc<Seat>>)> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(&token)?;
+        if seat.is_spectator() && session.seat.is_none() {
+            if let Err(err) = seat.promote() {
+                warn!("can't resume session: {err}");
+                return None;
+            }
+        }
+        session.generation = session.generation.wrapping_add(1);
+        let handle = Handle {
+            token,
+            generation: session.generation,
+        };
+        Some((session.snake_id, handle, session.seat.take()))
+    }
+
+    /// The socket holding `handle` is gone, its seat is kept until the session resumes or
+    /// closes. False when another socket took the session over already
+    pub fn disconnect(&self, handle: Handle, seat: Arc<Seat>) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(&handle.token) {
+            Some(session) if session.generation == handle.generation => {
+                session.seat = Some(seat);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Ends the session unless a socket took it over since `handle`, giving back the snake to
+    /// remove
+    pub fn close(&self, handle: Handle) -> Option<u16> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(&handle.token)?;
+        if session.generation != handle.generation {
+            return None;
+        }
+        let session = sessions.remove(&handle.token)?;
+        drop(sessions);
+        Some(session.snake_id)
+    }
+}
+
+fn new_token() -> SessionToken {
+    (random_seed() as SessionToken) << 64 | random_seed() as SessionToken
+}
@@ -1,15 +1,32 @@
 use crate::input_thread::rx_commands;
+use crate::maps::load_dir;
+use crate::outbox::{Outbox, SendStats, Sent, EVICT_AFTER, SEND_QUEUE};
+use crate::rooms::Seat;
+use crate::sessions::{Handle, Sessions};
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::SinkExt;
 use futures_util::StreamExt;
 use salvo::websocket::{Message, WebSocket};
 use snake::{
-    game::Game,
-    types::{Direction, GameConfig, GameState},
-    utils::encode,
+    delta::DeltaEncoder,
+    food::FoodRules,
+    game::{Game, GameData},
+    interest::Interest,
+    map::Map,
+    protocol::{ClientMessage, ServerMessage, SessionToken, PROTOCOL_VERSION},
+    replay::ReplayData,
+    rng::random_seed,
+    round::{Phase, Round, Step},
+    types::{Direction, FieldPoint, FoodContest, GameConfig, GameState, Wrap},
 };
 use std::{
-    sync::Arc,
-    time::{Duration, SystemTime},
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU16, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::watch;
 use tokio::{
@@ -17,12 +34,13 @@ use tokio::{
         watch::{Receiver, Sender},
         RwLock,
     },
-    time::{sleep, Instant},
+    time::{sleep, timeout, Instant},
 };
 use tracing::error;
 use tracing::error_span;
 use tracing::info_span;
 use tracing::span;
+use tracing::warn;
 use tracing::Level;
 
 const CONFIG: GameConfig = GameConfig {
@@ -30,109 +48,398 @@ const CONFIG: GameConfig = GameConfig {
     start: (1, 0),
     dim: (30, 20),
     direction: Direction::Right,
+    food_contest: FoodContest::Longer,
+    wrap: Wrap::Both,
+    seed: 0,
+    map: None,
+    food: FoodRules::DEFAULT,
 };
 const TICK_INTERVAL: u128 = 251 * 1000;
+/// Cells around its snake head each player gets, unless `VIEW` says otherwise
+const WINDOW: (u32, u32) = (64, 48);
+/// Tiles of the minimap, one per pixel of the panel sprite
+const MINIMAP: (u32, u32) = (8, 4);
+/// Frames between whole views, the ones in between only carry what changed
+const KEYFRAME_EVERY: u32 = 64;
+/// Time a new connection gets to say `Hello`
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time a snake stays in the game after its socket drops, unless `RECONNECT_GRACE` gives the
+/// seconds. No time at all removes it right away
+const RECONNECT_GRACE: Duration = Duration::from_secs(10);
+/// What every room starts from, read once from the environment
+#[derive(Debug, Clone)]
+pub struct Settings {
+    config: GameConfig,
+    replay_dir: Option<PathBuf>,
+    pub maps: Vec<Map>,
+    tick_interval: u128,
+    window: (u32, u32),
+    grace: Duration,
+    send_queue: usize,
+    evict_after: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let maps = std::env::var_os("MAP_DIR")
+            .map(|dir| load_dir(Path::new(&dir)))
+            .unwrap_or_default();
+        // MAP picks one by name, defaults to the first one
+        let map = match std::env::var("MAP") {
+            Ok(name) => maps.iter().find(|map| map.name == name),
+            Err(_) => maps.first(),
+        };
+        let tick_interval = map
+            .and_then(|map| map.tick)
+            .map_or(TICK_INTERVAL, |tick| tick as u128 * 1000);
+        let mut config = CONFIG;
+        // WORLD=WIDTHxHEIGHT makes a field larger than the screen, unless a map sets its size
+        if let Some(dim) = std::env::var("WORLD").ok().and_then(|dim| parse_dim(&dim)) {
+            config.dim = dim;
+        }
+        if let Some(map) = map {
+            config = config.with_map(map.clone());
+        }
+        if let Some(food) = std::env::var_os("FOOD_RULES").and_then(|path| load_rules(&path)) {
+            config.food = food;
+        }
+        let window = std::env::var("VIEW")
+            .ok()
+            .and_then(|dim| parse_dim(&dim))
+            .unwrap_or(WINDOW);
+        let grace = std::env::var("RECONNECT_GRACE")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map_or(RECONNECT_GRACE, Duration::from_secs);
+        let send_queue = std::env::var("SEND_QUEUE")
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .filter(|len| *len > 0)
+            .unwrap_or(SEND_QUEUE);
+        let evict_after = std::env::var("EVICT_AFTER")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map_or(EVICT_AFTER, Duration::from_secs);
+
+        Settings {
+            config,
+            replay_dir: std::env::var_os("REPLAY_DIR").map(PathBuf::from),
+            maps,
+            tick_interval,
+            window,
+            grace,
+            send_queue,
+            evict_after,
+        }
+    }
+}
+
+/// What both halves of a connection share
+#[derive(Debug, Default)]
+pub struct Connection {
+    /// Snake of the player, 0 while spectating
+    pub snake_id: AtomicU16,
+    /// Snake the spectator follows, 0 until the first one shows up
+    pub following: AtomicU16,
+    /// Spectator asking to play, hatched once the room is in the lobby
+    pub joining: AtomicBool,
+    /// Set when the client missed a frame and asks for a keyframe
+    pub resync: AtomicBool,
+    /// Players have one, taken by the first half of the connection to hang up
+    session: Mutex<Option<Handle>>,
+}
+
+impl Connection {
+    pub fn snake_id(&self) -> Option<u16> {
+        Some(self.snake_id.load(Ordering::Relaxed)).filter(|id| *id != 0)
+    }
+
+    fn play(&self, snake_id: u16, session: Handle) {
+        self.snake_id.store(snake_id, Ordering::Relaxed);
+        *self.session.lock().unwrap() = Some(session);
+    }
+
+    fn token(&self) -> Option<SessionToken> {
+        self.session.lock().unwrap().map(|session| session.token)
+    }
+}
+
+/// How a connection starts
+#[derive(Debug)]
+enum Hello {
+    New,
+    Resume(SessionToken),
+}
+
+/// What the tick loop hands every connection
+#[derive(Debug)]
+struct Tick {
+    interest: Interest,
+    phase: Phase,
+}
+
 #[derive(Debug)]
 pub struct WsGame {
+    /// Room the game is played in
+    pub code: String,
     pub game: Arc<RwLock<Game>>,
-    game_data_sender: Arc<RwLock<Sender<Message>>>,
-    game_data_receiver: Receiver<Message>,
+    pub round: Arc<RwLock<Round>>,
+    game_data_sender: Arc<RwLock<Sender<Arc<Tick>>>>,
+    game_data_receiver: Receiver<Arc<Tick>>,
+    replay_dir: Option<PathBuf>,
+    tick_interval: u128,
+    window: (u32, u32),
+    /// Cleared to stop the tick loop
+    running: Arc<AtomicBool>,
+    sessions: Sessions,
+    grace: Duration,
+    send_queue: usize,
+    evict_after: Duration,
+    /// Queues, dropped frames and evictions of every client
+    pub send_stats: Arc<SendStats>,
 }
 
-pub const GAME_DATA: u8 = 1;
-pub const NOTIFY: u8 = 2;
-pub const PING: u8 = 3;
-pub const DIRECTION: u8 = 4;
-
-impl Default for WsGame {
-    fn default() -> Self {
-        let game = Game::new(CONFIG);
+impl WsGame {
+    /// Every game gets its own seed
+    pub fn new(code: String, settings: &Settings) -> WsGame {
+        let config = GameConfig {
+            seed: random_seed(),
+            ..settings.config.clone()
+        };
+        let game = Game::new(config);
+        let round = Round::new(Duration::from_micros(settings.tick_interval as u64));
+        let window = settings.window;
+        let game_data = GameData::from_game(&game);
+        let game_data = Arc::new(Tick {
+            interest: Interest::new(game_data, window, MINIMAP),
+            phase: round.phase().clone(),
+        });
         let game = Arc::new(RwLock::new(game));
-        let (game_data_sender, game_data_receiver) = watch::channel(Message::binary(vec![]));
+        let (game_data_sender, game_data_receiver) = watch::channel(game_data);
 
         WsGame {
+            code,
             game,
+            round: Arc::new(RwLock::new(round)),
             game_data_sender: Arc::new(RwLock::new(game_data_sender)),
             game_data_receiver,
+            replay_dir: settings.replay_dir.clone(),
+            tick_interval: settings.tick_interval,
+            window,
+            running: Arc::new(AtomicBool::new(true)),
+            sessions: Sessions::default(),
+            grace: settings.grace,
+            send_queue: settings.send_queue,
+            evict_after: settings.evict_after,
+            send_stats: Arc::new(SendStats::default()),
         }
     }
-}
 
-impl WsGame {
-    pub async fn ingress_user(&self, ws: WebSocket) {
-        let (mut ws_tx, ws_rx) = ws.split();
+    /// The seat is held until the connection is gone. Spectators get no snake until they join,
+    /// a resumed session gets the snake it had
+    pub async fn ingress_user(&self, ws: WebSocket, seat: Seat) {
+        let mut seat = Arc::new(seat);
+        let (mut ws_tx, mut ws_rx) = ws.split();
+        let Some(hello) = handshake(&mut ws_tx, &mut ws_rx).await else {
+            return;
+        };
 
-        let snake_id = RwLock::write(&self.game).await.add_snake();
+        let connection = Arc::new(Connection::default());
+        let resumed = match hello {
+            Hello::Resume(token) => self.sessions.resume(token, &seat),
+            Hello::New => None,
+        };
+        if let Some((snake_id, session, kept)) = resumed {
+            // a spectator seat, as when the room was full, gives way to the one kept for the
+            // session. Whichever isn't used is let go
+            if let Some(kept) = kept.filter(|_kept| seat.is_spectator()) {
+                seat = kept;
+            }
+            connection.play(snake_id, session);
+        } else if !seat.is_spectator() {
+            match RwLock::write(&self.game).await.add_snake() {
+                Ok(snake_id) => connection.play(snake_id, self.sessions.open(snake_id)),
+                Err(err) => {
+                    // dropping the socket closes it
+                    error!("can't add snake: {err}");
+                    return;
+                }
+            }
+        }
         let game = Arc::clone(&self.game);
         let mut game_data_receiver = self.game_data_receiver.clone();
+        let state = Arc::clone(&connection);
+        let held = Arc::clone(&seat);
+        let stats = Arc::clone(&self.send_stats);
+        let mut outbox = Outbox::new(ws_tx, self.send_queue, self.evict_after, stats);
         tokio::task::spawn(async move {
-            let notify = Message::binary(to_command(NOTIFY, encode(snake_id).unwrap()));
-            if let Err(_msg) = ws_tx.send(notify).await {
-                RwLock::write(&game).await.remove_snake(snake_id);
+            let welcome = ServerMessage::Welcome {
+                version: PROTOCOL_VERSION,
+                snake_id: state.snake_id(),
+                token: state.token(),
+            };
+            if let Err(_closed) = outbox.send(&welcome).await {
+                hang_up(&state, &held).await;
+                return;
             }
+            // stays on the spot the snake died until it hatches again
+            let mut center = FieldPoint { x: 0, y: 0 };
+            let mut encoder = DeltaEncoder::new(KEYFRAME_EVERY);
+            let mut phase = None;
+            let mut following = None;
             while let Ok(()) = game_data_receiver.changed().await {
+                let tick = Arc::clone(&game_data_receiver.borrow_and_update());
+                let joined = match tick.phase {
+                    Phase::Lobby { .. } if state.joining.swap(false, Ordering::Relaxed) => {
+                        join(&game, &held).await
+                    }
+                    _ => None,
+                };
+                if let Some(snake_id) = joined {
+                    let session = held.room.sessions.open(snake_id);
+                    state.play(snake_id, session);
+                    let token = session.token;
+                    let player = ServerMessage::Player { snake_id, token };
+                    if let Err(_closed) = outbox.send(&player).await {
+                        break;
+                    }
+                }
+                let snake_id = state.snake_id();
                 let loop_span = span!(Level::INFO, "game_data", snake_id);
                 let _enter = loop_span.enter();
 
                 let game_span = info_span!("game_data");
-                let game_data = game_data_receiver.borrow_and_update().to_owned();
-
-                if let Err(_msg) = ws_tx.send(game_data).await {
-                    error_span!("game_data");
-                    break;
+                let interest = &tick.interest;
+                let snakes = &interest.data().snakes;
+                // spectators follow someone, the first snake until they pick another
+                let own = snake_id.or_else(|| {
+                    let followed = state.following.load(Ordering::Relaxed);
+                    match snakes.contains_key(&followed) {
+                        true => Some(followed),
+                        false => snakes.keys().next().copied(),
+                    }
+                });
+                if let Some(head) = own
+                    .and_then(|id| snakes.get(&id))
+                    .filter(|snake| snake.is_alive())
+                    .and_then(|s| s.nodes.back())
+                {
+                    center = head.position;
+                }
+                let view = interest.view(own, &center);
+                if state.resync.swap(false, Ordering::Relaxed) {
+                    encoder.resync();
+                }
+                let frame = ServerMessage::Frame(encoder.frame(view));
+                match outbox.push(&frame) {
+                    Ok(Sent::Queued) => {}
+                    // the client would miss what changed, the next frame is a whole view
+                    Ok(Sent::Dropped) => encoder.resync(),
+                    Err(_closed) => {
+                        error_span!("game_data");
+                        break;
+                    }
                 }
                 drop(game_span);
 
+                if phase.as_ref() != Some(&tick.phase) {
+                    let round = ServerMessage::Round(tick.phase.clone());
+                    if let Err(_closed) = outbox.send(&round).await {
+                        break;
+                    }
+                    phase = Some(tick.phase.clone());
+                }
+
+                if snake_id.is_none() && own != following {
+                    if let Some(followed) = own {
+                        state.following.store(followed, Ordering::Relaxed);
+                        let message = ServerMessage::Following(followed);
+                        if let Err(_closed) = outbox.send(&message).await {
+                            break;
+                        }
+                    }
+                    following = own;
+                }
+
                 let ping_span = info_span!("ping");
-                let ping = Message::binary(to_command(PING, encode(SystemTime::now()).unwrap()));
-                if let Err(_msg) = ws_tx.send(ping).await {
+                // dropped along with frames, a late pong would only tell the queue is long
+                if let Err(_closed) = outbox.push(&ServerMessage::Ping(now_micros())) {
                     // @todo how to send error spans
                     span!(Level::ERROR, "ping_error");
                     break;
                 }
                 drop(ping_span);
             }
+            if outbox.dropped() > 0 {
+                warn!(
+                    snake_id = state.snake_id(),
+                    dropped = outbox.dropped(),
+                    depth = outbox.depth(),
+                    "slow client"
+                );
+            }
 
-            let span = span!(Level::INFO, "remove", snake_id);
-
-            RwLock::write(&game).await.remove_snake(snake_id);
+            let span = span!(Level::INFO, "hang_up", snake_id = state.snake_id());
+            hang_up(&state, &held).await;
             drop(span);
         });
 
-        rx_commands(snake_id, ws_rx, Arc::clone(&self.game))
+        rx_commands(ws_rx, connection, seat)
     }
 
     pub fn start_game(&self) {
         let game_arc = Arc::clone(&self.game);
+        let round_arc = Arc::clone(&self.round);
         let game_data_sender = Arc::clone(&self.game_data_sender);
+        let replay_dir = self.replay_dir.clone();
+        let tick_interval = self.tick_interval;
+        let window = self.window;
+        let running = Arc::clone(&self.running);
+        let code = self.code.clone();
         let fut = async move {
-            {
-                let mut game = RwLock::write(&game_arc).await;
-                if game.state != GameState::None {
-                    // panic!("can't start game again")
-                    return;
-                }
-                game.state = GameState::Playing;
-            }
-            loop {
+            while running.load(Ordering::Relaxed) {
                 let now = Instant::now();
                 {
-                    let root = info_span!("game_loop");
+                    let root = info_span!("game_loop", room = %code);
                     let _enter = root.enter();
 
                     let mut game = RwLock::write(&game_arc).await;
-                    if game.state == GameState::Over {
-                        break;
+                    let mut round = RwLock::write(&round_arc).await;
+                    match round.tick(&game) {
+                        Some(Step::Start) => {
+                            game.state = GameState::Playing;
+                            if replay_dir.is_some() {
+                                game.start_recording();
+                            }
+                        }
+                        Some(Step::Over) => {
+                            if let (Some(dir), Some(data)) = (&replay_dir, game.stop_recording()) {
+                                save_replay(dir.clone(), &code, data);
+                            }
+                        }
+                        Some(Step::Restart) => {
+                            // everyone left mid-round, what was played is kept all the same
+                            if let (Some(dir), Some(data)) = (&replay_dir, game.stop_recording()) {
+                                save_replay(dir.clone(), &code, data);
+                            }
+                            *game = game.rematch(random_seed());
+                        }
+                        None => {}
                     }
 
-                    let span = info_span!("game_tick");
-                    game.tick();
-                    game.add_missing_food();
-                    drop(span);
+                    // snakes stay still outside of rounds
+                    if *round.phase() == Phase::Playing {
+                        let span = info_span!("game_tick");
+                        game.tick();
+                        drop(span);
+                    }
 
-                    let span = info_span!("encode_game_data");
-                    let game_data = game.encode_game_data();
-                    let game_data = Message::binary(to_command(GAME_DATA, game_data));
+                    let span = info_span!("index_game_data");
+                    let game_data = GameData::from_game(&game);
+                    let game_data = Arc::new(Tick {
+                        interest: Interest::new(game_data, window, MINIMAP),
+                        phase: round.phase().clone(),
+                    });
                     drop(span);
 
                     let span = info_span!("send_game_data");
@@ -144,18 +451,135 @@ impl WsGame {
                 }
 
                 let elapsed_micro = now.elapsed().as_micros();
-                if let Some(sleep_micros) = TICK_INTERVAL.checked_sub(elapsed_micro) {
+                if let Some(sleep_micros) = tick_interval.checked_sub(elapsed_micro) {
                     sleep(Duration::from_micros(sleep_micros as u64)).await;
                 }
             }
+            // the round the last players left in
+            let mut game = RwLock::write(&game_arc).await;
+            if let (Some(dir), Some(data)) = (&replay_dir, game.stop_recording()) {
+                save_replay(dir.clone(), &code, data);
+            }
         };
 
         tokio::spawn(fut);
     }
+
+    /// Ends the tick loop after the current tick
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Broken rules are logged and the defaults kept
+fn load_rules(path: &OsStr) -> Option<FoodRules> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            error!("can't read food rules {path:?} {err}");
+            return None;
+        }
+    };
+    match FoodRules::parse(&text) {
+        Ok(rules) => Some(rules),
+        Err(err) => {
+            error!("invalid food rules {path:?} {err}");
+            None
+        }
+    }
+}
+
+/// Each round goes to its own file, named after its room and the time it finished
+fn save_replay(dir: PathBuf, code: &str, data: ReplayData) {
+    let code = code.to_owned();
+    tokio::task::spawn_blocking(move || {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("{code}-{millis}.snkr"));
+        if let Err(err) = data.save(&path) {
+            error!("error saving replay {path:?} {err:?}");
+        }
+    });
+}
+
+fn parse_dim(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let width = width.trim().parse().ok().filter(|width| *width > 0)?;
+    let height = height.trim().parse().ok().filter(|height| *height > 0)?;
+    Some((width, height))
+}
+
+/// Both halves of a connection call it as they end, the first one to do so parks the session.
+/// The snake is removed once the grace period is over, unless a new socket resumed it
+pub async fn hang_up(connection: &Connection, seat: &Arc<Seat>) {
+    let Some(session) = connection.session.lock().unwrap().take() else {
+        return;
+    };
+    let room = Arc::clone(&seat.room);
+    if room.grace.is_zero() {
+        if let Some(snake_id) = room.sessions.close(session) {
+            RwLock::write(&room.game).await.remove_snake(snake_id);
+        }
+        return;
+    }
+    if !room.sessions.disconnect(session, Arc::clone(seat)) {
+        return;
+    }
+    tokio::task::spawn(async move {
+        sleep(room.grace).await;
+        if let Some(snake_id) = room.sessions.close(session) {
+            RwLock::write(&room.game).await.remove_snake(snake_id);
+        }
+    });
+}
+
+/// Waits for the client `Hello` or `Resume`, the ones speaking another version are told so and
+/// let go
+async fn handshake(
+    ws_tx: &mut SplitSink<WebSocket, Message>,
+    ws_rx: &mut SplitStream<WebSocket>,
+) -> Option<Hello> {
+    let hello = match timeout(HELLO_TIMEOUT, ws_rx.next()).await {
+        Ok(Some(Ok(msg))) => ClientMessage::from_bytes(msg.as_bytes()),
+        _ => return None,
+    };
+    match hello {
+        Ok(ClientMessage::Hello { version }) if version == PROTOCOL_VERSION => Some(Hello::New),
+        Ok(ClientMessage::Resume { version, token }) if version == PROTOCOL_VERSION => {
+            Some(Hello::Resume(token))
+        }
+        Ok(ClientMessage::Hello { version } | ClientMessage::Resume { version, .. }) => {
+            error!("client speaks protocol {version}");
+            let unsupported = ServerMessage::Unsupported {
+                version: PROTOCOL_VERSION,
+            };
+            let _ = ws_tx.send(Message::binary(unsupported.to_bytes())).await;
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Hatches the snake of a spectator, as long as there's a player seat and room on the field
+async fn join(game: &RwLock<Game>, seat: &Seat) -> Option<u16> {
+    if let Err(err) = seat.promote() {
+        warn!("spectator can't join: {err}");
+        return None;
+    }
+    match RwLock::write(game).await.add_snake() {
+        Ok(snake_id) => Some(snake_id),
+        Err(err) => {
+            // the player seat goes to someone who can take it
+            seat.demote();
+            warn!("spectator can't join: {err}");
+            None
+        }
+    }
 }
 
-fn to_command(id: u8, bytes: Vec<u8>) -> Vec<u8> {
-    let mut data: Vec<u8> = vec![id];
-    data.extend_from_slice(&bytes);
-    data
+fn now_micros() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    now.unwrap_or_default().as_micros() as u64
 }
@@ -31,6 +31,12 @@ export function drawSprite3x3(sprite, fieldX, fieldY) {
   drawSprite(sprite, x0, y0, 3, 3, translate);
 }
 
+export function drawSprite4x4(sprite, fieldX, fieldY) {
+  const [x0, y0] = [fieldX * BLOCK_PIXELS - 1, fieldY * BLOCK_PIXELS - 1];
+  clearFieldSprite(x0, y0, 4, 4);
+  drawSprite(sprite, x0, y0, 4, 4, translate);
+}
+
 export function drawSprite8x4(sprite, fieldX, fieldY) {
   const [x0, y0] = [fieldX * BLOCK_PIXELS - 1, fieldY * BLOCK_PIXELS - 1];
   clearFieldSprite(x0, y0, 8, 4);
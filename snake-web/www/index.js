@@ -1,25 +1,74 @@
 import init, { GameScene } from "./wasm/wasm_render.js";
 
-const COMMAND = {
-  LEFT: new Uint8Array([4, 0]),
-  UP: new Uint8Array([4, 1]),
-  RIGHT: new Uint8Array([4, 2]),
-  DOWN: new Uint8Array([4, 3]),
-};
-
 await init();
 const scene = GameScene.new();
 
 const protocol = location.protocol.replace("http", "ws");
-const ws = new WebSocket(`${protocol}//${location.host}/game_data`);
-
-ws.addEventListener("message", async function (msg) {
-  const data = new Uint8Array(await msg.data.arrayBuffer());
-  if (data[0] === 1) scene.set_data(data.slice(1));
-  if (data[0] === 2) scene.snake_id(data.slice(1));
-  if (data[0] === 3) return ws.send(data);
-  scene.draw();
-});
+// the page address picks the room and whether to play or watch, `?room=abc&spectate=1`
+const params = new URLSearchParams(location.search);
+// time before trying again once the connection drops
+const RECONNECT_DELAY = 1000;
+let ws;
+
+// a new connection resumes the session of the last one, keeping the same snake
+function connect() {
+  const query = new URLSearchParams(params);
+  // lets the server hand back the seat kept for the session when the room is full
+  if (scene.resumes()) query.set("resume", "1");
+  ws = new WebSocket(`${protocol}//${location.host}/game_data?${query}`);
+  let opened = false;
+  ws.addEventListener("open", () => {
+    opened = true;
+    ws.send(scene.hello());
+  });
+
+  // messages are all handled by the scene, which may have an answer to send back
+  ws.addEventListener("message", async function (msg) {
+    let reply;
+    try {
+      reply = scene.receive(new Uint8Array(await msg.data.arrayBuffer()));
+    } catch (err) {
+      return console.error(err);
+    }
+    if (reply) ws.send(reply);
+    scene.draw();
+  });
+
+  ws.addEventListener("close", async () => {
+    if (!scene.reconnects()) return console.error("server speaks another protocol");
+    if (!opened && (await roomIsFull())) return console.error("room is full");
+    setTimeout(connect, RECONNECT_DELAY);
+  });
+}
+
+// players are turned away from full rooms, spectators never are
+async function roomIsFull() {
+  const spectate = params.get("spectate");
+  if (spectate && spectate !== "0") return false;
+  const room = params.get("room") ?? "main";
+  try {
+    const res = await fetch(`/rooms/${encodeURIComponent(room)}`);
+    if (!res.ok) return false;
+    const info = await res.json();
+    return info.players >= info.max_players;
+  } catch {
+    return false;
+  }
+}
+
+connect();
+
+function turn(to) {
+  const message = scene.turn(to);
+  if (message && ws.readyState === WebSocket.OPEN) ws.send(message);
+}
+
+// players say they're ready for the next round with a tap, space or enter, spectators ask to
+// join it
+function ready() {
+  const message = scene.ready();
+  if (message && ws.readyState === WebSocket.OPEN) ws.send(message);
+}
 
 function toDirection(type) {
   switch (type) {
@@ -27,22 +76,22 @@ function toDirection(type) {
     case "ArrowLeft":
     case "KeyJ":
     case "KeyA":
-      return ws.send(COMMAND.LEFT);
+      return turn("left");
     case "panup":
     case "ArrowUp":
     case "KeyI":
     case "KeyW":
-      return ws.send(COMMAND.UP);
+      return turn("up");
     case "panright":
     case "ArrowRight":
     case "KeyL":
     case "KeyD":
-      return ws.send(COMMAND.RIGHT);
+      return turn("right");
     case "pandown":
     case "ArrowDown":
     case "KeyK":
     case "KeyS":
-      return ws.send(COMMAND.DOWN);
+      return turn("down");
   }
 }
 
@@ -51,7 +100,9 @@ hammertime.get("pan").set({ direction: Hammer.DIRECTION_ALL });
 hammertime.on("panleft panright panup pandown", function (ev) {
   toDirection(ev.type);
 });
+hammertime.on("tap", ready);
 
 document.addEventListener("keydown", (e) => {
+  if (e.code === "Space" || e.code === "Enter") return ready();
   toDirection(e.code);
 });
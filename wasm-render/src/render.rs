@@ -1,8 +1,11 @@
 use snake::{
+    effect::Effect,
     food::FoodField,
+    interest::Minimap,
     render::GameRender,
+    round::Phase,
     snake::{Snake, SnakeNode},
-    types::{Direction, FieldPoint, Food, FoodType, WrappableDirection},
+    types::{Direction, FieldPoint, Food, FoodType, Wrap, WrappableDirection},
 };
 
 use crate::{sprites::Sprites, types::Screen, utils::to_base_10_array};
@@ -14,9 +17,13 @@ macro_rules! log {
     }
 }
 
+/// Draws in field pixels, each cell spans 2x2 of them, and shows what falls within the camera
+/// viewport
 pub struct BinaryRender {
     pos: FieldPoint,
     to: WrappableDirection,
+    camera: FieldPoint,
+    view: FieldPoint,
     screen: Box<dyn Screen>,
 }
 
@@ -39,10 +46,12 @@ impl GameRender for BinaryRender {
             if prev.position == tail.position {
                 break;
             }
+            self.follow(prev, node.direction);
             let sprites = Sprites::full_node(prev.direction, node.direction, prev.stuffed);
             self.draw_snake_sprites(prev.direction, sprites);
-            node = &prev;
+            node = prev;
         }
+        self.follow(tail, node.direction);
         self.draw_snake_sprites(node.direction, Sprites::full_tail(node.direction));
     }
 
@@ -51,6 +60,9 @@ impl GameRender for BinaryRender {
             x: food.location.x * 2 + 1,
             y: food.location.y * 2 + 1,
         };
+        let Some(p) = self.on_screen(&p) else {
+            return;
+        };
         match food.shape {
             FoodType::Basic => self.screen.field_sprite_3x3(Sprites::food(), &p),
             x => {
@@ -63,42 +75,130 @@ impl GameRender for BinaryRender {
     fn score(&mut self, score: u16) {
         self.draw_panel_digits(score, 4, 0);
     }
+
+    fn wall(&mut self, p: &FieldPoint) {
+        if let Some(p) = self.on_screen(&center(p)) {
+            self.screen.field_sprite_4x4(Sprites::wall(), &p);
+        }
+    }
+
+    fn portal(&mut self, p: &FieldPoint) {
+        if let Some(p) = self.on_screen(&center(p)) {
+            self.screen.field_sprite_4x4(Sprites::portal(), &p);
+        }
+    }
 }
 
 impl BinaryRender {
-    pub fn new(width: u16, height: u16, screen: Box<dyn Screen>) -> BinaryRender {
+    /// The canvas takes the size of the viewport, `view` cells wide and tall
+    pub fn new(width: u32, height: u32, view: (u32, u32), screen: Box<dyn Screen>) -> BinaryRender {
         let max = FieldPoint {
             x: width * 2,
             y: height * 2,
         };
-        screen.setup(max.x, max.y);
+        let view = FieldPoint {
+            x: view.0 * 2,
+            y: view.1 * 2,
+        };
+        screen.setup(view.x as u16, view.y as u16);
         let to = Direction::Right;
-        let to = WrappableDirection { max, to };
+        let wrap = Wrap::Both;
+        let to = WrappableDirection { max, to, wrap };
         BinaryRender {
             screen,
             pos: max,
             to,
+            camera: FieldPoint { x: 0, y: 0 },
+            view,
         }
     }
+    /// Top left cell of the viewport
+    pub fn set_camera(&mut self, origin: FieldPoint) {
+        self.camera = FieldPoint {
+            x: origin.x * 2,
+            y: origin.y * 2,
+        };
+    }
+    /// Where a field pixel lands on the canvas, going around the field edges
+    fn on_screen(&self, p: &FieldPoint) -> Option<FieldPoint> {
+        let max = self.to.max;
+        let x = (p.x + max.x - self.camera.x) % max.x;
+        let y = (p.y + max.y - self.camera.y) % max.y;
+        (x < self.view.x && y < self.view.y).then_some(FieldPoint { x, y })
+    }
     fn head_at(&mut self, head: &SnakeNode) {
-        let p = head.position;
         self.go_to(&SnakeNode {
             direction: head.direction,
             stuffed: false,
-            position: FieldPoint {
-                x: (p.x * 2) + 1,
-                y: (p.y * 2) + 1,
-            },
+            position: center(&head.position),
         });
     }
+    /// Stepping back lands on the node unless the body went through a wormhole. Then it jumps
+    /// over to the node and draws the half block going into the portal
+    fn follow(&mut self, node: &SnakeNode, to: Direction) {
+        let p = center(&node.position);
+        if self.pos == p {
+            return;
+        }
+        self.turn(to);
+        self.pos = p.wrapping_add(self.to);
+        self.draw_snake_sprite(Sprites::block(to));
+        self.pos = p;
+    }
     pub fn draw_food_ticker(&mut self, food: &Food, idx: u8) {
         let sprite = Sprites::special_food(food.shape);
+        self.draw_ticker(sprite, food.ticks_left as u16, idx);
+    }
+    /// Same spot as the food tickers, the inverted sprite tells effects apart
+    pub fn draw_effect_ticker(&mut self, effect: &Effect, idx: u8) {
+        let sprite = Sprites::effect(effect.kind);
+        self.draw_ticker(sprite, effect.ticks_left as u16, idx);
+    }
+    /// Where the round is at, after the food and effect tickers
+    pub fn draw_round(&mut self, phase: &Phase, own: Option<u16>, idx: u8) {
+        match phase {
+            Phase::Lobby { ready, players } => {
+                let own_ready = own.is_some_and(|id| ready.contains(&id));
+                self.draw_ticker(Sprites::ready(own_ready), ready.len() as u16, idx);
+                self.draw_ticker(Sprites::players(), *players, idx + 1);
+            }
+            Phase::Countdown(seconds) => {
+                self.draw_ticker(Sprites::countdown(), *seconds as u16, idx);
+            }
+            Phase::Playing => {}
+            Phase::RoundOver(standings) => {
+                let place = standings.iter().position(|s| Some(s.id) == own);
+                let place = place.map_or(0, |place| place as u16 + 1);
+                self.draw_ticker(Sprites::trophy(), place, idx);
+            }
+        }
+    }
+    /// An 8x4 sprite followed by a two digit number, from the right end of the panel
+    fn draw_ticker(&mut self, sprite: u32, n: u16, idx: u8) {
         self.screen
             .panel_sprite_8x4(sprite, -(14 + 20 * idx as i16), 2);
-        self.draw_panel_digits(food.ticks_left as u16, 2, -(2 + idx as i16 * 5));
+        self.draw_panel_digits(n, 2, -(2 + idx as i16 * 5));
+    }
+    /// Tiles with snakes are lit and the own one blinks, right after the score
+    pub fn draw_minimap(&mut self, minimap: &Minimap, own: Option<usize>, blink: bool) {
+        let mut sprite: u32 = 0;
+        for (tile, heads) in minimap.snakes.iter().enumerate() {
+            let lit = match own == Some(tile) {
+                true => blink,
+                false => *heads > 0,
+            };
+            if lit {
+                let x = (tile as u32 % minimap.columns) * 8 / minimap.columns;
+                let y = (tile as u32 / minimap.columns) * 4 / minimap.rows;
+                sprite |= 1 << (31 - (y * 8 + x));
+            }
+        }
+        self.screen.panel_sprite_8x4(sprite, 18, 2);
     }
     fn draw_snake_sprite(&mut self, sprite: u8) {
-        let p = self.pos;
+        let Some(p) = self.on_screen(&self.pos) else {
+            return;
+        };
         match self.to.to {
             Direction::Left | Direction::Right => self.screen.field_sprite_2x4(sprite, &p),
             Direction::Up | Direction::Down => self.screen.field_sprite_4x2(sprite, &p),
@@ -129,3 +229,11 @@ impl BinaryRender {
         self.pos = self.pos.wrapping_sub(self.to);
     }
 }
+
+/// Each field cell spans 2x2 render positions, sprites are placed on its center
+fn center(p: &FieldPoint) -> FieldPoint {
+    FieldPoint {
+        x: p.x * 2 + 1,
+        y: p.y * 2 + 1,
+    }
+}
@@ -1,18 +1,36 @@
 use snake::{
-    game::GameData,
+    delta::DeltaDecoder,
+    error::Error,
+    interest::View,
+    protocol::{ClientMessage, ServerMessage, SessionToken, PROTOCOL_VERSION},
     render::GameRender,
-    types::{FoodType, GameConfig},
-    utils::decode,
+    round::Phase,
+    types::{Direction, FieldPoint, FoodType, GameConfig},
 };
 use wasm_bindgen::prelude::*;
 
 use crate::{render::BinaryRender, screen::CanvasScreen};
 
+/// Cells shown at once, larger fields scroll along with the own snake
+const VIEWPORT: (u32, u32) = (30, 20);
+
 #[wasm_bindgen]
 pub struct GameScene {
+    /// None while spectating
     snake_id: Option<u16>,
-    data: Option<GameData>,
+    /// Takes the snake back over after the connection drops
+    token: Option<SessionToken>,
+    /// Snake a spectator follows, the panel shows its score and effects
+    following: Option<u16>,
+    frames: DeltaDecoder,
     render: BinaryRender,
+    camera: FieldPoint,
+    /// Flips every tick, the own tile on the minimap blinks with it
+    blink: bool,
+    /// Where the round is at, as last told by the server
+    phase: Option<Phase>,
+    /// Cleared when the server speaks another protocol, reconnecting won't change that
+    reconnects: bool,
 }
 
 #[allow(unused_macros)]
@@ -34,27 +52,137 @@ impl GameScene {
         let config = GameConfig::default();
         let (width, height) = config.dim;
         let screen = CanvasScreen {};
-        let render = BinaryRender::new(width, height, Box::new(screen));
+        let render = BinaryRender::new(width, height, viewport(width, height), Box::new(screen));
         GameScene {
-            data: None,
+            frames: DeltaDecoder::default(),
             render,
             snake_id: None,
+            token: None,
+            following: None,
+            camera: FieldPoint { x: 0, y: 0 },
+            blink: false,
+            phase: None,
+            reconnects: true,
+        }
+    }
+    /// First message on a new connection, resuming the session of the last one if any
+    pub fn hello(&self) -> Vec<u8> {
+        let version = PROTOCOL_VERSION;
+        match self.token {
+            Some(token) => ClientMessage::Resume { version, token }.to_bytes(),
+            None => ClientMessage::Hello { version }.to_bytes(),
         }
     }
-    pub fn snake_id(&mut self, data: Vec<u8>) {
-        let (snake_id, _size): (u16, usize) = decode(&data).unwrap();
-        self.snake_id = Some(snake_id);
+
+    /// There's a session to take over on the next connection
+    pub fn resumes(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Worth connecting again once the connection drops
+    pub fn reconnects(&self) -> bool {
+        self.reconnects
+    }
+
+    /// Takes a message from the server, along with what to answer it if anything. Messages
+    /// that can't be taken throw, the scene stays as it was
+    pub fn receive(&mut self, bytes: Vec<u8>) -> Result<Option<Vec<u8>>, JsError> {
+        let message = ServerMessage::from_bytes(&bytes).map_err(to_js)?;
+        let reply = match message {
+            ServerMessage::Welcome {
+                snake_id, token, ..
+            } => {
+                // frames of the new connection start over
+                self.frames = DeltaDecoder::default();
+                self.snake_id = snake_id;
+                self.token = token;
+                None
+            }
+            ServerMessage::Unsupported { version } => {
+                log!("server speaks protocol {version}, this page {PROTOCOL_VERSION}");
+                self.reconnects = false;
+                None
+            }
+            ServerMessage::Frame(frame) => match self.frames.apply(frame) {
+                Ok(()) => {
+                    self.set_render();
+                    None
+                }
+                Err(Error::FrameGap { .. }) => Some(ClientMessage::Keyframe),
+                Err(err) => return Err(to_js(err)),
+            },
+            ServerMessage::Ping(sent) => Some(ClientMessage::Pong(sent)),
+            ServerMessage::Round(phase) => {
+                self.phase = Some(phase);
+                None
+            }
+            ServerMessage::Player { snake_id, token } => {
+                self.snake_id = Some(snake_id);
+                self.token = Some(token);
+                None
+            }
+            ServerMessage::Following(snake_id) => {
+                self.following = Some(snake_id);
+                None
+            }
+        };
+        Ok(reply.map(|message| message.to_bytes()))
+    }
+
+    /// `left`, `up`, `right` or `down`. Spectators follow the previous snake going left and
+    /// the next one going right
+    pub fn turn(&self, to: &str) -> Option<Vec<u8>> {
+        let to: Direction = to.parse().ok()?;
+        let message = match (self.snake_id, to) {
+            (Some(_), to) => ClientMessage::Turn(to),
+            (None, Direction::Left) => ClientMessage::FollowPrevious,
+            (None, Direction::Right) => ClientMessage::FollowNext,
+            (None, _) => return None,
+        };
+        Some(message.to_bytes())
+    }
+
+    /// Only the lobby waits for players to be ready, spectators ask to play the next round
+    pub fn ready(&self) -> Option<Vec<u8>> {
+        match (self.snake_id, &self.phase) {
+            (None, _) => Some(ClientMessage::Join.to_bytes()),
+            (Some(_), Some(Phase::Lobby { .. })) => Some(ClientMessage::Ready.to_bytes()),
+            _ => None,
+        }
     }
 
     pub fn draw(&mut self) {
         unsafe { clearField() };
-        if let Some(data) = &self.data {
+        if let Some(View { data, minimap }) = self.frames.view() {
+            let focus = self.snake_id.or(self.following);
+            let own = focus.and_then(|id| data.snakes.get(&id));
+            let head = own
+                .filter(|snake| snake.is_alive())
+                .and_then(|snake| snake.nodes.back());
+            if let Some(head) = head {
+                self.camera = look_at(&head.position, &data.config);
+            }
+            let (width, height) = data.config.dim;
+            if viewport(width, height) != data.config.dim {
+                let tile = head.map(|head| minimap.tile_of(&head.position, &data.config));
+                self.render.draw_minimap(minimap, tile, self.blink);
+            }
+            self.render.set_camera(self.camera);
             for (_id, snake) in data.snakes.iter() {
-                self.render.snake(snake, &data.food);
-                if self.snake_id.eq(&Some(snake.id)) {
+                if snake.is_alive() {
+                    self.render.snake(snake, &data.food);
+                }
+                if focus == Some(snake.id) {
                     self.render.score(snake.score);
                 }
             }
+            for wall in data.walls.iter() {
+                self.render.wall(wall);
+            }
+            for (a, b) in data.wormholes.iter() {
+                self.render.portal(a);
+                self.render.portal(b);
+            }
             let mut special_idx = 0;
             for food in data.food.foods.iter() {
                 self.render.food(food);
@@ -63,15 +191,50 @@ impl GameScene {
                     special_idx += 1;
                 }
             }
+            // effects on the own snake go right after the food tickers
+            for effect in own.iter().flat_map(|snake| snake.effects.iter()) {
+                self.render.draw_effect_ticker(effect, special_idx);
+                special_idx += 1;
+            }
+            if let Some(phase) = &self.phase {
+                self.render.draw_round(phase, focus, special_idx);
+            }
         }
     }
+}
 
-    pub fn set_data(&mut self, data: Vec<u8>) {
-        let (data, _size): (GameData, usize) = decode(&data).unwrap();
-        let (width, height) = data.config.dim;
-        let screen = CanvasScreen {};
-        self.render = BinaryRender::new(width, height, Box::new(screen));
-        self.data = Some(data);
+impl GameScene {
+    /// The canvas follows the size of the field, which changes along with the map
+    fn set_render(&mut self) {
+        if let Some(View { data, .. }) = self.frames.view() {
+            let (width, height) = data.config.dim;
+            let screen = CanvasScreen {};
+            let view = viewport(width, height);
+            self.render = BinaryRender::new(width, height, view, Box::new(screen));
+        }
+        self.blink = !self.blink;
+    }
+}
+
+fn to_js(err: Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+fn viewport(width: u32, height: u32) -> (u32, u32) {
+    (width.min(VIEWPORT.0), height.min(VIEWPORT.1))
+}
+
+/// Top left cell of a viewport centered on `p`. Edges that don't wrap stop the camera
+fn look_at(p: &FieldPoint, config: &GameConfig) -> FieldPoint {
+    let (width, height) = config.dim;
+    let (view_x, view_y) = viewport(width, height);
+    let axis = |p: u32, len: u32, view: u32, wraps: bool| match wraps {
+        true => (p + len - view / 2) % len,
+        false => p.saturating_sub(view / 2).min(len - view),
+    };
+    FieldPoint {
+        x: axis(p.x, width, view_x, config.wrap.wraps_x()),
+        y: axis(p.y, height, view_y, config.wrap.wraps_y()),
     }
 }
 
@@ -8,16 +8,19 @@ impl Screen for CanvasScreen {
         unsafe { setup(width, height) };
     }
     fn field_sprite_4x2(&self, sprite: u8, p: &FieldPoint) {
-        unsafe { drawSprite4x2(sprite.reverse_bits(), p.x, p.y) };
+        unsafe { drawSprite4x2(sprite.reverse_bits(), p.x as u16, p.y as u16) };
     }
     fn field_sprite_2x4(&self, sprite: u8, p: &FieldPoint) {
-        unsafe { drawSprite2x4(sprite.reverse_bits(), p.x, p.y) };
+        unsafe { drawSprite2x4(sprite.reverse_bits(), p.x as u16, p.y as u16) };
     }
     fn field_sprite_3x3(&self, sprite: u8, p: &FieldPoint) {
-        unsafe { drawSprite3x3(sprite.reverse_bits(), p.x, p.y) };
+        unsafe { drawSprite3x3(sprite.reverse_bits(), p.x as u16, p.y as u16) };
+    }
+    fn field_sprite_4x4(&self, sprite: u16, p: &FieldPoint) {
+        unsafe { drawSprite4x4(sprite.reverse_bits(), p.x as u16, p.y as u16) };
     }
     fn field_sprite_8x4(&self, sprite: u32, p: &FieldPoint) {
-        unsafe { drawSprite8x4(sprite.reverse_bits(), p.x, p.y) };
+        unsafe { drawSprite8x4(sprite.reverse_bits(), p.x as u16, p.y as u16) };
     }
     fn panel_sprite_3x5(&self, sprite: u16, px: i16) {
         unsafe { drawPanelSprite3x5(sprite.reverse_bits(), px) };
@@ -33,6 +36,7 @@ extern "C" {
     fn drawSprite4x2(sprite: u8, px: u16, py: u16);
     fn drawSprite2x4(sprite: u8, px: u16, py: u16);
     fn drawSprite3x3(sprite: u8, px: u16, py: u16);
+    fn drawSprite4x4(sprite: u16, px: u16, py: u16);
     fn drawSprite8x4(sprite: u32, px: u16, py: u16);
 }
 #[link(wasm_import_module = "/canvas/panel.js")]
@@ -1,4 +1,7 @@
-use snake::types::{Direction, FoodType};
+use snake::{
+    effect::EffectKind,
+    types::{Direction, FoodType},
+};
 
 pub struct Sprites {}
 
@@ -7,6 +10,12 @@ impl Sprites {
     pub fn food() -> u8 {
         0b010_101_01
     }
+    pub fn wall() -> u16 {
+        0b1111_1001_1001_1111
+    }
+    pub fn portal() -> u16 {
+        0b0110_1001_1001_0110
+    }
     pub fn special_food(element: FoodType) -> u32 {
         match element {
             FoodType::Whale => 0b00001100_10011010_10111110_01111111,
@@ -18,6 +27,26 @@ impl Sprites {
             _ => 0b11111111_11111111_11111111_11111111,
         }
     }
+    pub fn effect(kind: EffectKind) -> u32 {
+        !Sprites::special_food(kind.food())
+    }
+    /// A tick, inverted until the player is ready
+    pub fn ready(ready: bool) -> u32 {
+        let tick = 0b00000011_00000110_11001100_01111000;
+        match ready {
+            true => tick,
+            false => !tick,
+        }
+    }
+    pub fn players() -> u32 {
+        0b00000000_01100110_11111111_01100110
+    }
+    pub fn countdown() -> u32 {
+        0b11111111_00111100_00111100_11111111
+    }
+    pub fn trophy() -> u32 {
+        0b10111101_01111110_00011000_00111100
+    }
     pub fn digit(digit: u8) -> u16 {
         match digit {
             0 => 0b111_101_101_101_111_0,